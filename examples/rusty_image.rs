@@ -0,0 +1,168 @@
+use arkirust::cli::{AgentApp, AppFakts};
+use arkirust::fakts::fakts_protocol::Manifest;
+use arkirust::fakts::fakts_protocol::Requirement;
+use arkirust::mikro::client::MikroClient;
+use arkirust::mikro::datalayer::DatalayerClient;
+use arkirust::mikro::fakt::DatalayerFakt;
+use arkirust::mikro::fakt::MikroFakt;
+use arkirust::mikro::upload::create_image;
+use arkirust::rekuest::api::create_template::NodeKind;
+use arkirust::rekuest::client::RekuestClient;
+use arkirust::rekuest::context::Context;
+use arkirust::rekuest::definition::Definition;
+use arkirust::rekuest::fakt::RekuestFakt;
+use arkirust::rekuest::ports::Port;
+use arkirust::rekuest::registry::FunctionRegistry;
+use arkirust::rekuest::template::Template;
+use arkirust::rekuest::testing::{test_for, NodeTest};
+use arkirust::unlok::client::UnlokClient;
+use arkirust::unlok::fakt::UnlokFakt;
+
+use ndarray::Array;
+use ndarray_rand::rand::SeedableRng;
+use ndarray_rand::rand_distr::Uniform;
+use ndarray_rand::RandomExt;
+use rand_isaac::isaac64::Isaac64Rng;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ExampleFuncArgs {
+    name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ExampleFuncReturns {
+    image: String,
+}
+
+async fn example_func(
+    ctx: Context<App>,
+    args: ExampleFuncArgs,
+) -> anyhow::Result<ExampleFuncReturns> {
+    let mut rng = Isaac64Rng::seed_from_u64(42);
+
+    let shape = (1, 1, 1, 1000, 1000);
+    let array = Array::random_using(shape, Uniform::new(0, 100), &mut rng);
+
+    let app = ctx.state();
+    ctx.progress(50, Some("Uploading array")).await;
+    let image = create_image(app.mikro.clone(), app.datalayer.clone(), array, args.name).await?;
+
+    println!("Image: {:?}", image);
+    let image = image
+        .data
+        .ok_or_else(|| anyhow::anyhow!("fromArrayLike failed: {:?}", image.errors))?;
+    Ok(ExampleFuncReturns {
+        image: image.from_array_like.id,
+    })
+}
+
+/// Creates an image called "test" and expects an image id back
+#[test_for(example_func)]
+fn creates_named_image() -> NodeTest {
+    NodeTest::new(&ExampleFuncArgs {
+        name: "test".to_string(),
+    })
+    .check(|returns: ExampleFuncReturns| {
+        if returns.image.is_empty() {
+            return Err("no image id returned".to_string());
+        }
+        Ok(())
+    })
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct ExpectedFakts {
+    unlok: UnlokFakt,
+    rekuest: RekuestFakt,
+    mikro: MikroFakt,
+    datalayer: DatalayerFakt,
+}
+
+impl AppFakts for ExpectedFakts {
+    fn unlok(&self) -> &UnlokFakt {
+        &self.unlok
+    }
+
+    fn rekuest(&self) -> &RekuestFakt {
+        &self.rekuest
+    }
+}
+
+struct App {
+    rekuest: RekuestClient,
+    unlok: UnlokClient,
+    mikro: MikroClient,
+    datalayer: DatalayerClient,
+}
+impl Clone for App {
+    fn clone(&self) -> Self {
+        App {
+            rekuest: self.rekuest.clone(),
+            unlok: self.unlok.clone(),
+            mikro: self.mikro.clone(),
+            datalayer: self.datalayer.clone(),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // fakts
+
+    let manifest = Manifest {
+        identifier: "my-app".to_string(),
+        version: "0.1.0".to_string(),
+        scopes: vec!["read".to_string()],
+        requirements: vec![
+            Requirement {
+                key: "unlok".to_string(),
+                service: "live.arkitekt.lok".to_string(),
+                optional: false,
+            },
+            Requirement {
+                key: "rekuest".to_string(),
+                service: "live.arkitekt.rekuest".to_string(),
+                optional: false,
+            },
+            Requirement {
+                key: "mikro".to_string(),
+                service: "live.arkitekt.mikro".to_string(),
+                optional: false,
+            },
+            Requirement {
+                key: "datalayer".to_string(),
+                service: "live.arkitekt.s3".to_string(),
+                optional: false,
+            },
+        ],
+    };
+
+    let function_def = Definition::new("Create Rusty image", NodeKind::FUNCTION)
+        .description("Creates a really rusty image (unfortunatly only zarr v3")
+        .args(vec![Port::new_string("name").build()])
+        .returns(vec![Port::new_structure("image", "@mikro/image").build()])
+        .try_build()?;
+
+    let template_input = Template::new("rusty-image", function_def).build();
+
+    let mut registry = FunctionRegistry::new();
+    registry.register(template_input, example_func)?;
+    creates_named_image::register(&mut registry)?;
+
+    // e.g. `cargo run --example rusty_image -- serve`, or
+    // `-- run rusty-image --arg name=foo` to call the function once
+    AgentApp::new(manifest, registry, |fakts: &ExpectedFakts, token: &str| {
+        Ok(App {
+            rekuest: RekuestClient::new(fakts.rekuest.clone(), token)?,
+            unlok: UnlokClient::new(fakts.unlok.clone(), token)?,
+            mikro: MikroClient::new(fakts.mikro.clone(), token)?,
+            datalayer: DatalayerClient::new(fakts.mikro.clone(), fakts.datalayer.clone(), token)?,
+        })
+    })
+    .name("My beautiful rust agent")
+    .run()
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,383 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Expr, Fields, FnArg, GenericArgument, ItemFn, Pat,
+    PathArguments, ReturnType, Type,
+};
+
+/// Describes a function as JSON through a generated `inspect()`.
+///
+/// Arguments may carry `#[validate(...)]` and `#[effect(...)]` attributes
+/// holding `Validator` and `Effect` expressions. They are collected into
+/// generated `validators()` and `effects()` functions returning the
+/// expressions per argument key, ready to be passed to the port builders.
+/// `Validator` and `Effect` have to be in scope where the macro is used.
+#[proc_macro_attribute]
+pub fn json_types(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as ItemFn);
+
+    let vis = &input.vis;
+    let sig = &input.sig;
+    let func_name = &sig.ident;
+    let block = &input.block;
+
+    // Extract parameter info
+    let mut params = Vec::new();
+    let mut call_inputs = Vec::new();
+    let mut validators = Vec::new();
+    let mut effects = Vec::new();
+    for arg in &sig.inputs {
+        if let FnArg::Typed(pat_type) = arg {
+            if let Pat::Ident(pat_ident) = &*pat_type.pat {
+                let arg_name_str = pat_ident.ident.unraw().to_string();
+
+                let ty = &pat_type.ty;
+                let type_str = quote!(#ty).to_string();
+
+                let type_str = type_str.trim();
+                let arg_type_str = match () {
+                    _ if [
+                        "i8", "i16", "i32", "i64", "i128", "u8", "u16", "u32", "u64", "u128",
+                    ]
+                    .iter()
+                    .any(|&x| type_str.contains(x)) =>
+                    {
+                        "int"
+                    }
+                    _ if ["f32", "f64"].iter().any(|&x| type_str.contains(x)) => "float",
+                    _ if type_str.contains("bool") => "bool",
+                    _ if type_str.contains("String") || type_str.contains("str") => "string",
+                    _ if type_str.contains("Vec") || type_str.contains("Array") => "list",
+                    _ if type_str.contains("HashMap") || type_str.contains("Map") => "dict",
+                    _ => "String",
+                }
+                .to_string();
+
+                for attr in &pat_type.attrs {
+                    let target = if attr.path().is_ident("validate") {
+                        &mut validators
+                    } else if attr.path().is_ident("effect") {
+                        &mut effects
+                    } else {
+                        continue;
+                    };
+                    match attr.parse_args::<Expr>() {
+                        Ok(expr) => target.push((arg_name_str.clone(), expr)),
+                        Err(err) => return err.to_compile_error().into(),
+                    }
+                }
+
+                params.push((arg_name_str, arg_type_str));
+                let pat = &pat_ident.ident;
+                let ty = &pat_type.ty;
+                call_inputs.push(quote! { #pat: #ty });
+            } else {
+                return syn::Error::new_spanned(
+                    arg,
+                    "Only simple identifier parameters are supported.",
+                )
+                .to_compile_error()
+                .into();
+            }
+        } else {
+            return syn::Error::new_spanned(
+                arg,
+                "Methods with a `self` receiver are not supported.",
+            )
+            .to_compile_error()
+            .into();
+        }
+    }
+
+    // Determine return type
+    let return_type_str = match &sig.output {
+        ReturnType::Default => "void".to_string(),
+        ReturnType::Type(_, ty) => {
+            let type_str = quote!(#ty).to_string();
+            match () {
+                _ if [
+                    "i8", "i16", "i32", "i64", "i128", "u8", "u16", "u32", "u64", "u128",
+                ]
+                .iter()
+                .any(|&x| type_str.contains(x)) =>
+                {
+                    "int"
+                }
+                _ if ["f32", "f64"].iter().any(|&x| type_str.contains(x)) => "float",
+                _ if type_str.contains("bool") => "bool",
+                _ if type_str.contains("String") || type_str.contains("str") => "string",
+                _ if type_str.contains("Vec") || type_str.contains("Array") => "list",
+                _ if type_str.contains("HashMap") || type_str.contains("Map") => "dict",
+                _ => "String",
+            }
+            .to_string()
+        }
+    };
+
+    // Describe the parameters and return type as JSON
+    let args: Vec<serde_json::Value> = params
+        .iter()
+        .map(|(name, kind)| serde_json::json!({ "name": name, "kind": kind }))
+        .collect();
+    let json_str = serde_json::json!({
+        "name": func_name.unraw().to_string(),
+        "args": args,
+        "return_type": return_type_str,
+    })
+    .to_string();
+    let json_literal = syn::LitStr::new(&json_str, proc_macro2::Span::call_site());
+
+    let output_type = match &sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+
+    let validators_fn = per_key_fn(vis, "validators", "Validator", &validators);
+    let effects_fn = per_key_fn(vis, "effects", "Effect", &effects);
+
+    let expanded = quote! {
+        #[allow(non_camel_case_types)]
+        #vis struct #func_name;
+
+        impl #func_name {
+            #vis fn inspect() -> &'static str {
+                #json_literal
+            }
+
+            #vis fn call(#(#call_inputs),*) -> #output_type {
+                #block
+            }
+
+            #validators_fn
+            #effects_fn
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+/// Generates `fn #name() -> Vec<(&'static str, Vec<#ty>)>` grouping `exprs`
+/// by argument key, or nothing if there are no expressions.
+fn per_key_fn(
+    vis: &syn::Visibility,
+    name: &str,
+    ty: &str,
+    exprs: &[(String, Expr)],
+) -> proc_macro2::TokenStream {
+    if exprs.is_empty() {
+        return quote!();
+    }
+
+    let mut keys: Vec<&String> = Vec::new();
+    for (key, _) in exprs {
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    let entries = keys.iter().map(|key| {
+        let key_exprs = exprs.iter().filter(|(k, _)| k == *key).map(|(_, e)| e);
+        quote! { (#key, vec![#(#key_exprs),*]) }
+    });
+
+    let name = syn::Ident::new(name, proc_macro2::Span::call_site());
+    let ty = syn::Ident::new(ty, proc_macro2::Span::call_site());
+    quote! {
+        #vis fn #name() -> Vec<(&'static str, Vec<#ty>)> {
+            vec![#(#entries),*]
+        }
+    }
+}
+
+/// Implements `arkirust::rekuest::state::StateSchema` for a struct with
+/// named fields, describing every field as a port keyed by its name.
+///
+/// Integers, floats, `bool` and `String` map to their port kinds, `Vec<T>`
+/// to a LIST and `HashMap<String, T>`/`BTreeMap<String, T>` to a DICT port.
+/// `Option<T>` makes the port nullable.
+#[proc_macro_derive(StateSchema)]
+pub fn derive_state_schema(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    let ident = &input.ident;
+    let name = ident.to_string();
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return syn::Error::new_spanned(ident, "StateSchema needs named fields")
+                    .to_compile_error()
+                    .into()
+            }
+        },
+        _ => {
+            return syn::Error::new_spanned(ident, "StateSchema can only be derived for structs")
+                .to_compile_error()
+                .into()
+        }
+    };
+
+    let mut ports = Vec::new();
+    for field in fields {
+        let key = field.ident.as_ref().unwrap().to_string();
+        match port_expr(&key, &field.ty) {
+            Ok(port) => ports.push(quote! { #port.build() }),
+            Err(e) => return e.to_compile_error().into(),
+        }
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let expanded = quote! {
+        impl #impl_generics ::arkirust::rekuest::state::StateSchema for #ident #ty_generics #where_clause {
+            const NAME: &'static str = #name;
+
+            fn ports() -> Vec<::arkirust::rekuest::api::create_template::PortInput> {
+                vec![#(#ports),*]
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+/// The port builder expression describing a value of type `ty` under `key`.
+fn port_expr(key: &str, ty: &Type) -> syn::Result<proc_macro2::TokenStream> {
+    let unsupported = || {
+        syn::Error::new_spanned(
+            ty,
+            "unsupported state field type, expected a number, bool, String, Option, Vec or map",
+        )
+    };
+
+    let segment = match ty {
+        Type::Path(path) => path.path.segments.last().ok_or_else(unsupported)?,
+        _ => return Err(unsupported()),
+    };
+    let generics: Vec<&Type> = match &segment.arguments {
+        PathArguments::AngleBracketed(args) => args
+            .args
+            .iter()
+            .filter_map(|arg| match arg {
+                GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let port = quote!(::arkirust::rekuest::ports::Port);
+    let expr = match (segment.ident.to_string().as_str(), generics.as_slice()) {
+        ("Option", [inner]) => {
+            let inner = port_expr(key, inner)?;
+            quote! { #inner.nullable(true) }
+        }
+        (
+            "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64" | "u128"
+            | "usize",
+            [],
+        ) => quote! { #port::new_int(#key) },
+        ("f32" | "f64", []) => quote! { #port::new_float(#key) },
+        ("bool", []) => quote! { #port::new_bool(#key) },
+        ("String", []) => quote! { #port::new_string(#key) },
+        ("Vec", [item]) => {
+            let child = port_expr("item", item)?;
+            quote! { #port::new_list(#key, #child) }
+        }
+        ("HashMap" | "BTreeMap", [_, value]) => {
+            let child = port_expr("value", value)?;
+            quote! { #port::new_dict(#key, #child) }
+        }
+        _ => return Err(unsupported()),
+    };
+
+    Ok(expr)
+}
+
+/// Turns a function returning a `NodeTest` into a test of a registered
+/// node, named either by the function registered for it,
+/// e.g. `#[test_for(create_rusty_image)]`, or by its interface,
+/// e.g. `#[test_for("rusty-image")]`.
+///
+/// The function form links the test to the tested function at compile
+/// time, the interface is looked up with `FunctionRegistry::interface_of`
+/// when the test is registered. The interface form must be spelled exactly
+/// as the node was registered.
+///
+/// The function is replaced by a unit struct of the same name with a
+/// `case()` function returning the test and a `register(&mut registry)`
+/// function adding it to a `FunctionRegistry`. Doc comments become the
+/// test's description.
+#[proc_macro_attribute]
+pub fn test_for(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let interface = if let Ok(lit) = syn::parse::<syn::LitStr>(attr.clone()) {
+        if lit.value().is_empty() {
+            return syn::Error::new_spanned(lit, "test_for needs the interface of the tested node")
+                .to_compile_error()
+                .into();
+        }
+        quote! { #lit.to_string() }
+    } else {
+        match syn::parse::<syn::Path>(attr) {
+            Ok(path) => {
+                let function = quote!(#path).to_string().replace(' ', "");
+                quote! {
+                    registry
+                        .interface_of(&#path)
+                        .map(str::to_string)
+                        .ok_or_else(|| format!("{} is not registered", #function))?
+                }
+            }
+            Err(e) => {
+                return syn::Error::new(
+                    e.span(),
+                    "test_for takes the tested function or its interface, \
+                     e.g. #[test_for(create_rusty_image)] or #[test_for(\"rusty-image\")]",
+                )
+                .to_compile_error()
+                .into()
+            }
+        }
+    };
+
+    let input = parse_macro_input!(item as ItemFn);
+    let vis = &input.vis;
+    let name = &input.sig.ident;
+    let name_str = name.to_string();
+    let output = &input.sig.output;
+    let block = &input.block;
+
+    let description = input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(meta) => match &meta.value {
+                Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(doc),
+                    ..
+                }) => Some(doc.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let expanded = quote! {
+        #[allow(non_camel_case_types)]
+        #vis struct #name;
+
+        impl #name {
+            #vis fn case() #output #block
+
+            #vis fn register<S: Clone + Send + Sync + 'static>(
+                registry: &mut ::arkirust::rekuest::registry::FunctionRegistry<S>,
+            ) -> Result<(), Box<dyn std::error::Error>> {
+                let interface: String = #interface;
+                registry.add_test(&interface, #name_str, #description, Self::case())
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
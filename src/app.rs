@@ -1,5 +0,0 @@
-#[derive(Deserialize, Serialize, Debug)]
-struct ExpectedFakts {
-    unlok: UnlokFakt,
-    rekuest: RekuestFakt,
-}
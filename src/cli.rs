@@ -0,0 +1,430 @@
+use std::error::Error;
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::pin::pin;
+
+use clap::{Args, Parser, Subcommand};
+use futures::TryStreamExt;
+use serde::de::DeserializeOwned;
+
+use crate::fakts::fakts_protocol::Manifest;
+use crate::fakts::funcs::{
+    forget_token, register_client_at, DEFAULT_FAKTS_URL, DEFAULT_TOKEN_PATH,
+};
+use crate::rekuest::admin::{delete_agent, list_agents, AgentFilter};
+use crate::rekuest::agent::{create_agent, AgentBuilder};
+use crate::rekuest::call::{CallEvent, Target};
+use crate::rekuest::client::{RekuestClient, DEFAULT_INSTANCE_ID};
+use crate::rekuest::fakt::RekuestFakt;
+use crate::rekuest::local::{read_args, split_arg, LocalRun};
+use crate::rekuest::manifest::{ManifestFormat, TemplateManifest};
+use crate::rekuest::registry::{server_templates, FunctionRegistry, TemplateDrift};
+use crate::unlok::fakt::UnlokFakt;
+use crate::unlok::token::get_auth_token;
+
+/// The fakts an app built with `AgentApp` needs, usually a struct with
+/// `unlok` and `rekuest` fields next to the app's own services.
+pub trait AppFakts: DeserializeOwned + Debug {
+    fn unlok(&self) -> &UnlokFakt;
+    fn rekuest(&self) -> &RekuestFakt;
+}
+
+/// Builds the application state from the claimed fakts and an access token.
+pub type StateFactory<F, S> = Box<dyn Fn(&F, &str) -> Result<S, Box<dyn Error>>>;
+
+/// Builds the application state without any server, for `run`.
+pub type LocalStateFactory<S> = Box<dyn Fn() -> Result<S, Box<dyn Error>>>;
+
+/// The command line of an arkirust app.
+#[derive(Parser, Debug)]
+#[command(version, about = "Run and manage an arkirust agent")]
+pub struct Cli {
+    #[command(flatten)]
+    pub options: CliOptions,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// Flags shared by all commands.
+#[derive(Args, Debug, Clone)]
+pub struct CliOptions {
+    /// The fakts server to register with.
+    #[arg(long, global = true, default_value = DEFAULT_FAKTS_URL)]
+    pub url: String,
+    /// The instance id of the agent.
+    #[arg(long, global = true, default_value = DEFAULT_INSTANCE_ID)]
+    pub instance_id: String,
+    /// Where the fakts token is saved.
+    #[arg(long, global = true, default_value = DEFAULT_TOKEN_PATH)]
+    pub token_file: PathBuf,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Register the app with the fakts server and save the granted token.
+    Login,
+    /// Forget the saved token.
+    Logout,
+    /// Compare and sync the registered templates with the server.
+    #[command(subcommand)]
+    Templates(TemplatesCommand),
+    /// List and clean up the agents on the server.
+    #[command(subcommand)]
+    Agents(AgentsCommand),
+    /// Print the registered templates as a manifest.
+    Inspect {
+        /// Print YAML instead of JSON.
+        #[arg(long)]
+        yaml: bool,
+        /// Write the manifest to this file, in the format of its extension.
+        #[arg(long, conflicts_with = "check")]
+        out: Option<PathBuf>,
+        /// Fail if the registered definitions differ from this manifest.
+        #[arg(long, value_name = "MANIFEST")]
+        check: Option<PathBuf>,
+    },
+    /// Sync the templates and serve assignations until the connection closes.
+    Serve,
+    /// Assign to a node and print its events and returns.
+    Call {
+        /// A registered interface, or the hash of any node.
+        target: String,
+        #[command(flatten)]
+        args: ArgsOptions,
+    },
+    /// Call a registered function in-process, without the rekuest server.
+    /// The state comes from `AgentApp::local_state` if the app sets one;
+    /// otherwise it is built from the fakts, which needs a login.
+    Run {
+        interface: String,
+        #[command(flatten)]
+        args: ArgsOptions,
+        /// Write the returns to this file instead of printing them.
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TemplatesCommand {
+    /// List the templates the agent has on the server.
+    List,
+    /// Show how the registered templates differ from the server's.
+    Diff,
+    /// Fail if the server's templates differ from a manifest.
+    Check {
+        /// A manifest written by `inspect --out`.
+        manifest: PathBuf,
+    },
+    /// Push the registered templates to the server.
+    Sync {
+        /// Delete templates the app no longer registers.
+        #[arg(long)]
+        cleanup: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AgentsCommand {
+    /// List the agents of this app's client.
+    List,
+    /// Delete this app's agents that are neither connected nor pinned, e.g.
+    /// left behind by crashed test runs. Only prints them without `--yes`.
+    Prune {
+        /// Only agents whose instance id starts with this prefix.
+        #[arg(long, value_name = "PREFIX")]
+        matching: Option<String>,
+        /// Delete the agents instead of printing them.
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+/// Arguments of a node.
+#[derive(Args, Debug, Clone)]
+pub struct ArgsOptions {
+    /// An argument as `key=value`, may be repeated.
+    #[arg(long = "arg", value_name = "KEY=VALUE", value_parser = split_arg)]
+    pub args: Vec<(String, String)>,
+    /// A JSON file holding a map of arguments.
+    #[arg(long = "args", value_name = "FILE")]
+    pub file: Option<PathBuf>,
+}
+
+/// An app built on arkirust: its manifest, its functions and how to build
+/// its state. `run` dispatches the command line to it.
+pub struct AgentApp<F, S> {
+    manifest: Manifest,
+    name: String,
+    extension: String,
+    registry: FunctionRegistry<S>,
+    state: StateFactory<F, S>,
+    local_state: Option<LocalStateFactory<S>>,
+}
+
+impl<F: AppFakts, S: Clone + Send + Sync + 'static> AgentApp<F, S> {
+    pub fn new<B>(manifest: Manifest, registry: FunctionRegistry<S>, state: B) -> Self
+    where
+        B: Fn(&F, &str) -> Result<S, Box<dyn Error>> + 'static,
+    {
+        Self {
+            name: manifest.identifier.clone(),
+            manifest,
+            extension: "default".to_string(),
+            registry,
+            state: Box::new(state),
+            local_state: None,
+        }
+    }
+
+    /// Build the state for `run` with `state` instead of from the fakts, so
+    /// functions can be run without logging in or reaching any server.
+    pub fn local_state<B>(mut self, state: B) -> Self
+    where
+        B: Fn() -> Result<S, Box<dyn Error>> + 'static,
+    {
+        self.local_state = Some(Box::new(state));
+        self
+    }
+
+    /// The name of the agent, defaults to the manifest identifier.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// The extension the templates are synced for, defaults to `default`.
+    pub fn extension(mut self, extension: &str) -> Self {
+        self.extension = extension.to_string();
+        self
+    }
+
+    /// Parse the process arguments and run the command.
+    pub async fn run(self) -> Result<(), Box<dyn Error>> {
+        self.run_with(Cli::parse()).await
+    }
+
+    pub async fn run_with(self, cli: Cli) -> Result<(), Box<dyn Error>> {
+        let AgentApp {
+            manifest,
+            name,
+            extension,
+            mut registry,
+            state,
+            local_state,
+        } = self;
+        let options = cli.options;
+
+        // Commands that need no server.
+        match &cli.command {
+            Command::Logout => {
+                match forget_token(&options.token_file)? {
+                    true => println!("Logged out"),
+                    false => println!("Not logged in"),
+                }
+                return Ok(());
+            }
+            Command::Inspect { yaml, out, check } => {
+                let manifest = registry.manifest()?;
+                if let Some(path) = check {
+                    return check_drift(manifest.compare(&TemplateManifest::load(path)?));
+                }
+                match out {
+                    Some(path) => manifest.save(path)?,
+                    None => {
+                        let format = match yaml {
+                            true => ManifestFormat::Yaml,
+                            false => ManifestFormat::Json,
+                        };
+                        print!("{}", manifest.to_string(format)?);
+                    }
+                }
+                return Ok(());
+            }
+            Command::Run {
+                interface,
+                args,
+                out,
+            } => {
+                if let Some(local_state) = &local_state {
+                    let run = LocalRun {
+                        interface: interface.clone(),
+                        args: args.args.clone(),
+                        args_file: args.file.clone(),
+                        out: out.clone(),
+                    };
+                    registry.run_local(local_state()?, &run).await?;
+                    return Ok(());
+                }
+            }
+            _ => {}
+        }
+
+        let fakts: F = register_client_at(&options.url, &options.token_file, manifest).await?;
+        if let Command::Login = cli.command {
+            println!("Logged in to {}", options.url);
+            return Ok(());
+        }
+
+        let token = get_auth_token(fakts.unlok().clone()).await?;
+        let client = RekuestClient::new(fakts.rekuest().clone(), &token)?
+            .with_instance_id(&options.instance_id);
+
+        match cli.command {
+            Command::Login | Command::Logout | Command::Inspect { .. } => Ok(()),
+            Command::Templates(command) => {
+                // Only `sync` creates the agent, the others only look it up.
+                let existing = || {
+                    let filter = AgentFilter {
+                        instance_id: Some(options.instance_id.clone()),
+                        client_id: Some(fakts.unlok().client_id.clone()),
+                        ..Default::default()
+                    };
+                    list_agents(&client, filter).map_ok(|agent| agent.id)
+                };
+                match command {
+                    TemplatesCommand::List => {
+                        let Some(agent) = pin!(existing()).try_next().await? else {
+                            println!("No agent {} on the server yet", options.instance_id);
+                            return Ok(());
+                        };
+                        for template in server_templates(&client, &agent, &extension).await? {
+                            println!(
+                                "{}\t{}\t{}",
+                                template.interface, template.node.hash, template.id
+                            );
+                        }
+                    }
+                    TemplatesCommand::Diff => {
+                        let Some(agent) = pin!(existing()).try_next().await? else {
+                            println!("No agent {} on the server yet", options.instance_id);
+                            return Ok(());
+                        };
+                        let drift = registry.drift(&client, &agent, &extension).await?;
+                        if drift.is_empty() {
+                            println!("Templates of {} are up to date", extension);
+                        }
+                        for drift in drift {
+                            println!("{}", drift);
+                        }
+                    }
+                    TemplatesCommand::Check { manifest } => {
+                        let manifest = TemplateManifest::load(&manifest)?;
+                        let Some(agent) = pin!(existing()).try_next().await? else {
+                            return Err(format!(
+                                "No agent {} on the server yet",
+                                options.instance_id
+                            )
+                            .into());
+                        };
+                        check_drift(manifest.diff(&client, &agent, &extension).await?)?;
+                    }
+                    TemplatesCommand::Sync { cleanup } => {
+                        let agent =
+                            create_agent(&client, &options.instance_id, &name, vec![&extension])
+                                .await?;
+                        registry.sync(&client, &agent, &extension, cleanup).await?;
+                    }
+                }
+                Ok(())
+            }
+            Command::Agents(command) => {
+                // Only the agents of this app, never those of other clients.
+                let filter = AgentFilter {
+                    client_id: Some(fakts.unlok().client_id.clone()),
+                    ..Default::default()
+                };
+                // Collected first, deleting while paging would skip agents.
+                let agents: Vec<_> = list_agents(&client, filter).try_collect().await?;
+                for agent in agents {
+                    match &command {
+                        AgentsCommand::List => println!(
+                            "{}\t{}\t{}\t{:?}{}",
+                            agent.id,
+                            agent.instance_id,
+                            agent.name,
+                            agent.status,
+                            if agent.pinned { "\tpinned" } else { "" }
+                        ),
+                        AgentsCommand::Prune { matching, yes } => {
+                            let matches = matching
+                                .as_ref()
+                                .is_none_or(|prefix| agent.instance_id.starts_with(prefix));
+                            if agent.connected || agent.pinned || !matches {
+                                continue;
+                            }
+                            if *yes {
+                                println!("Deleting {} ({})", agent.name, agent.instance_id);
+                                delete_agent(&client, &agent.id).await?;
+                            } else {
+                                println!("Would delete {} ({})", agent.name, agent.instance_id);
+                            }
+                        }
+                    }
+                }
+                if let AgentsCommand::Prune { yes: false, .. } = command {
+                    println!("Pass --yes to delete them");
+                }
+                Ok(())
+            }
+            Command::Serve => {
+                let state = state(&fakts, &token)?;
+                AgentBuilder::with_client(client, fakts.rekuest().clone())
+                    .name(&name)
+                    .extension(&extension, registry, state)
+                    .run()
+                    .await?;
+                Ok(())
+            }
+            Command::Call { target, args } => {
+                let ports = registry
+                    .template_for(&target)
+                    .map(|template| template.definition.args.as_slice());
+                let args = read_args(&target, ports, &args.args, args.file.as_deref())?;
+                let hash = registry.hash(&target).unwrap_or(target);
+
+                let mut call = client.call(Target::hash(&hash), &args).await?;
+                while let Some(event) = call.next_event().await {
+                    match event? {
+                        CallEvent::Yield(returns) => {
+                            println!("{}", serde_json::to_string_pretty(&returns)?)
+                        }
+                        CallEvent::Log { message, .. } => println!("{}", message),
+                        CallEvent::Progress { progress, message } => println!(
+                            "[{:>3}%] {}",
+                            progress.unwrap_or_default(),
+                            message.unwrap_or_default()
+                        ),
+                    }
+                }
+                Ok(())
+            }
+            Command::Run {
+                interface,
+                args,
+                out,
+            } => {
+                let run = LocalRun {
+                    interface,
+                    args: args.args,
+                    args_file: args.file,
+                    out,
+                };
+                registry.run_local(state(&fakts, &token)?, &run).await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Print `drift` and fail if there is any.
+fn check_drift(drift: Vec<TemplateDrift>) -> Result<(), Box<dyn Error>> {
+    if drift.is_empty() {
+        println!("Templates match the manifest");
+        return Ok(());
+    }
+    for drift in &drift {
+        println!("{}", drift);
+    }
+    Err(format!("{} templates differ from the manifest", drift.len()).into())
+}
@@ -1,11 +1,22 @@
 use super::fakts_protocol::{
     DeviceCodeAnswer, DeviceCodeChallengeAnswer, DeviceCodeChallengeRequest,
-    DeviceCodeStartRequest, FaktsAnswer, Manifest, Requirement, RetrieveRequest, TokenConfig,
+    DeviceCodeStartRequest, FaktsAnswer, Manifest, RetrieveRequest, TokenConfig,
 };
 
+/// The fakts server used when none is given.
+pub const DEFAULT_FAKTS_URL: &str = "http://127.0.0.1";
+
+/// Where the fakts token is saved when no path is given.
+pub const DEFAULT_TOKEN_PATH: &str = "token.json";
+
 pub async fn get_saved_token() -> Result<Option<String>, Box<dyn std::error::Error>> {
-    // read save token form token.json
-    let token_path = std::path::Path::new("token.json");
+    get_saved_token_at(std::path::Path::new(DEFAULT_TOKEN_PATH)).await
+}
+
+/// Read the token saved at `token_path`, if any.
+pub async fn get_saved_token_at(
+    token_path: &std::path::Path,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
     // parse token from json according to a struct with token field
     if !token_path.exists() {
         return Ok(None);
@@ -17,10 +28,18 @@ pub async fn get_saved_token() -> Result<Option<String>, Box<dyn std::error::Err
 
 pub async fn claim_fakts<T: ::serde::de::DeserializeOwned + std::fmt::Debug>(
     token: String,
+) -> Result<T, Box<dyn std::error::Error>> {
+    claim_fakts_at(DEFAULT_FAKTS_URL, token).await
+}
+
+/// Claim the fakts granted to `token` from the fakts server at `url`.
+pub async fn claim_fakts_at<T: ::serde::de::DeserializeOwned + std::fmt::Debug>(
+    url: &str,
+    token: String,
 ) -> Result<T, Box<dyn std::error::Error>> {
     let client = reqwest::Client::new();
     let retrieve_response = client
-        .post("http://127.0.0.1/lok/f/claim/")
+        .post(format!("{}/lok/f/claim/", url))
         .json(&RetrieveRequest {
             token: token.clone(),
         })
@@ -38,30 +57,46 @@ pub async fn claim_fakts<T: ::serde::de::DeserializeOwned + std::fmt::Debug>(
     };
 
     println!("Response from register_client: {:?}", fakts_answer);
-    return Ok(fakts_answer.config);
+    Ok(fakts_answer.config)
 }
 
 pub async fn register_client<T: ::serde::de::DeserializeOwned + std::fmt::Debug>(
     manifest: Manifest,
+) -> Result<T, Box<dyn std::error::Error>> {
+    register_client_at(
+        DEFAULT_FAKTS_URL,
+        std::path::Path::new(DEFAULT_TOKEN_PATH),
+        manifest,
+    )
+    .await
+}
+
+/// Claim fakts from the fakts server at `url` with the token saved at
+/// `token_path`, or register `manifest` with the device code flow and save
+/// the granted token there.
+pub async fn register_client_at<T: ::serde::de::DeserializeOwned + std::fmt::Debug>(
+    url: &str,
+    token_path: &std::path::Path,
+    manifest: Manifest,
 ) -> Result<T, Box<dyn std::error::Error>> {
     // Try to retrive from saved token (if any)
 
-    let token = get_saved_token().await?;
+    let token = get_saved_token_at(token_path).await?;
     if let Some(token) = token {
-        match claim_fakts(token).await {
-            Ok(fakts) => return Ok(fakts),
-            Err(_) => (), // Continue with rest of function if error occurs
-        };
+        // Continue with rest of function if error occurs
+        if let Ok(fakts) = claim_fakts_at(url, token).await {
+            return Ok(fakts);
+        }
     }
 
     let request = DeviceCodeStartRequest {
-        manifest: manifest,
+        manifest,
         requested_client_kind: "development".to_string(),
     };
 
     let client = reqwest::Client::new();
     let res = client
-        .post("http://127.0.0.1/lok/f/start/")
+        .post(format!("{}/lok/f/start/", url))
         .json(&request)
         .send()
         .await?;
@@ -71,7 +106,10 @@ pub async fn register_client<T: ::serde::de::DeserializeOwned + std::fmt::Debug>
     // Parse the response body into a DeviceCodeAnswer struct
     let device_code_answer: DeviceCodeAnswer = serde_json::from_str(&body)?;
 
-    println!("Response from register_client: http://127.0.0.1/lok/f/configure/?grant=device_code&device_code={}", device_code_answer.code);
+    println!(
+        "Response from register_client: {}/lok/f/configure/?grant=device_code&device_code={}",
+        url, device_code_answer.code
+    );
 
     // Check if the challenge has been accepted for a while
     // and if the status is still pending
@@ -86,7 +124,7 @@ pub async fn register_client<T: ::serde::de::DeserializeOwned + std::fmt::Debug>
 
     loop {
         let res = client
-            .post("http://127.0.0.1/lok/f/challenge/")
+            .post(format!("{}/lok/f/challenge/", url))
             .json(&challenge)
             .send()
             .await?;
@@ -122,8 +160,6 @@ pub async fn register_client<T: ::serde::de::DeserializeOwned + std::fmt::Debug>
     };
 
     // Save token to token.json
-    let token_path = std::path::Path::new("token.json");
-
     let token_data = TokenConfig {
         token: token.clone(),
     };
@@ -131,5 +167,15 @@ pub async fn register_client<T: ::serde::de::DeserializeOwned + std::fmt::Debug>
 
     std::fs::write(token_path, token_json)?;
 
-    Ok(claim_fakts(token).await?)
+    claim_fakts_at(url, token).await
+}
+
+/// Forget the token saved at `token_path`, so the next registration goes
+/// through the device code flow again. Returns whether there was one.
+pub fn forget_token(token_path: &std::path::Path) -> Result<bool, Box<dyn std::error::Error>> {
+    if !token_path.exists() {
+        return Ok(false);
+    }
+    std::fs::remove_file(token_path)?;
+    Ok(true)
 }
@@ -41,7 +41,7 @@ impl DatalayerClient {
         Ok(Self {
             client,
             endpoint_url: fakt.endpoint_url.clone(),
-            datalayer_fakt: datalayer_fakt,
+            datalayer_fakt,
         })
     }
 
@@ -55,7 +55,7 @@ impl DatalayerClient {
         let credentials_request: graphql_client::QueryBody<api::request_upload::Variables> =
             api::RequestUpload::build_query(api::request_upload::Variables {
                 input: api::request_upload::RequestUploadInput {
-                    key: key,
+                    key,
                     datalayer: "default".to_string(),
                 },
             });
@@ -76,7 +76,7 @@ impl DatalayerClient {
         let object_store = AmazonS3Builder::new()
             .with_allow_http(true)
             .with_bucket_name(credentials.bucket)
-            .with_endpoint(format!("http://127.0.0.1").as_str())
+            .with_endpoint("http://127.0.0.1")
             .with_access_key_id(credentials.access_key)
             .with_secret_access_key(credentials.secret_key)
             .with_token(credentials.session_token)
@@ -6,23 +6,11 @@ use super::datalayer;
 use anyhow::Error;
 use graphql_client::GraphQLQuery;
 use graphql_client::Response;
-use ndarray::Array;
 use ndarray::Array5;
-use ndarray_rand::rand::SeedableRng;
-use ndarray_rand::rand_distr::Uniform;
-use ndarray_rand::RandomExt;
-use object_store::aws::AmazonS3Builder;
-use rand_isaac::isaac64::Isaac64Rng;
 use zarrs::array::codec::GzipCodec;
 use zarrs::array::ArrayBuilder;
 use zarrs::array::DataType;
 use zarrs::array::FillValue;
-use zarrs::filesystem::FilesystemStore;
-use zarrs::group::GroupBuilder;
-use zarrs::{array_subset::ArraySubset, node::Node};
-use zarrs_object_store::object_store::ObjectStore;
-use zarrs_object_store::AsyncObjectStore;
-use zarrs_storage::AsyncReadableWritableListableStorage;
 
 pub async fn create_image(
     mikro: MikroClient,
@@ -86,7 +74,7 @@ pub async fn create_image(
         api::FromArrayLike::build_query(api::from_array_like::Variables {
             input: api::from_array_like::FromArrayLikeInput {
                 array: store.store_id,
-                name: name,
+                name,
                 dataset: None,
                 acquisition_views: None,
                 channel_views: None,
@@ -110,5 +98,5 @@ pub async fn create_image(
             println!("Deserialization error: {}", e);
             e
         })?;
-    return Ok(body);
+    Ok(body)
 }
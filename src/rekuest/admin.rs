@@ -0,0 +1,417 @@
+//! Administrative access to agents, templates and provisions, e.g. to clean
+//! up agents left behind by crashed test runs:
+//!
+//! ```ignore
+//! let stale = AgentFilter { pinned: Some(false), ..Default::default() };
+//! // Collect first: deleting while paging shifts the offsets.
+//! let agents: Vec<Agent> = list_agents(&client, stale).try_collect().await?;
+//! for agent in agents.iter().filter(|agent| !agent.connected) {
+//!     delete_agent(&client, &agent.id).await?;
+//! }
+//! ```
+
+use std::collections::VecDeque;
+use std::future::Future;
+
+use futures::Stream;
+use graphql_client::GraphQLQuery;
+use graphql_client::QueryBody;
+use graphql_client::Response;
+
+use super::api::delete_agent;
+use super::api::delete_template;
+use super::api::list_agents;
+use super::api::list_provisions;
+use super::api::list_templates;
+use super::api::pin_agent;
+use super::api::pin_template;
+use super::api::reinit;
+use super::api::unprovide;
+use super::api::{DeleteAgent, DeleteTemplate, ListAgents, ListProvisions, ListTemplates};
+use super::api::{PinAgent, PinTemplate, Reinit, Unprovide};
+use super::client::RekuestClient;
+
+pub use super::api::list_agents::ListAgentsAgents as Agent;
+pub use super::api::list_provisions::ListProvisionsProvisions as Provision;
+pub use super::api::list_provisions::ProvisionStatus;
+pub use super::api::list_templates::ListTemplatesTemplates as AgentTemplate;
+
+/// How many items the listing streams fetch per request.
+pub const PAGE_SIZE: i64 = 100;
+
+/// Which agents to list. Unset fields match every agent.
+#[derive(Debug, Clone, Default)]
+pub struct AgentFilter {
+    pub ids: Option<Vec<String>>,
+    pub instance_id: Option<String>,
+    pub client_id: Option<String>,
+    /// Agents with any of these extensions.
+    pub extensions: Option<Vec<String>>,
+    pub pinned: Option<bool>,
+}
+
+/// Which templates to list. Unset fields match every template.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateFilter {
+    pub ids: Option<Vec<String>>,
+    /// The exact interface.
+    pub interface: Option<String>,
+    pub extension: Option<String>,
+    pub node_hash: Option<String>,
+    /// Templates of the agents with this instance id.
+    pub instance_id: Option<String>,
+}
+
+/// Which provisions to list. Unset fields match every provision.
+#[derive(Debug, Clone, Default)]
+pub struct ProvisionFilter {
+    pub ids: Option<Vec<String>>,
+    pub status: Option<Vec<ProvisionStatus>>,
+    /// Provisions of the agents matching this filter.
+    pub agent: Option<AgentFilter>,
+}
+
+impl AgentFilter {
+    fn to_input(&self) -> list_agents::AgentFilter {
+        list_agents::AgentFilter {
+            ids: self.ids.clone(),
+            instance_id: self.instance_id.clone(),
+            client_id: self.client_id.clone(),
+            extensions: self.extensions.clone(),
+            pinned: self.pinned,
+            ..Default::default()
+        }
+    }
+}
+
+impl TemplateFilter {
+    fn to_input(&self) -> list_templates::TemplateFilter {
+        list_templates::TemplateFilter {
+            ids: self.ids.clone(),
+            interface: self.interface.as_ref().map(|interface| exactly(interface)),
+            extension: self.extension.clone(),
+            node_hash: self.node_hash.clone(),
+            agent: Box::new(self.instance_id.as_ref().map(|instance_id| {
+                list_templates::TemplateAgentFilter {
+                    client_id: None,
+                    instance_id: Some(instance_id.clone()),
+                    ids: None,
+                    extensions: None,
+                    has_templates: None,
+                    has_states: None,
+                    and: Box::new(None),
+                    or: Box::new(None),
+                }
+            })),
+            node: Box::new(None),
+            parameters: None,
+            and: Box::new(None),
+            or: Box::new(None),
+        }
+    }
+}
+
+/// A lookup matching `value` exactly. The template filters are spelled out
+/// in full: their `PortDemandInput` has a required `DemandKind`, which has
+/// no sensible default.
+fn exactly(value: &str) -> list_templates::StrFilterLookup {
+    list_templates::StrFilterLookup {
+        exact: Some(value.to_string()),
+        i_exact: None,
+        contains: None,
+        i_contains: None,
+        in_list: None,
+        gt: None,
+        gte: None,
+        lt: None,
+        lte: None,
+        starts_with: None,
+        i_starts_with: None,
+        ends_with: None,
+        i_ends_with: None,
+        range: None,
+        is_null: None,
+        regex: None,
+        i_regex: None,
+        n_exact: None,
+        n_i_exact: None,
+        n_contains: None,
+        n_i_contains: None,
+        n_in_list: None,
+        n_gt: None,
+        n_gte: None,
+        n_lt: None,
+        n_lte: None,
+        n_starts_with: None,
+        n_i_starts_with: None,
+        n_ends_with: None,
+        n_i_ends_with: None,
+        n_range: None,
+        n_is_null: None,
+        n_regex: None,
+        n_i_regex: None,
+    }
+}
+
+impl ProvisionFilter {
+    fn to_input(&self) -> list_provisions::ProvisionFilter {
+        list_provisions::ProvisionFilter {
+            ids: self.ids.clone(),
+            status: self.status.clone(),
+            agent: Box::new(
+                self.agent
+                    .as_ref()
+                    .map(|agent| list_provisions::AgentFilter {
+                        ids: agent.ids.clone(),
+                        instance_id: agent.instance_id.clone(),
+                        client_id: agent.client_id.clone(),
+                        extensions: agent.extensions.clone(),
+                        pinned: agent.pinned,
+                        ..Default::default()
+                    }),
+            ),
+            ..Default::default()
+        }
+    }
+}
+
+/// All agents matching `filter`, fetched page by page.
+pub fn list_agents(
+    client: &RekuestClient,
+    filter: AgentFilter,
+) -> impl Stream<Item = anyhow::Result<Agent>> {
+    let client = client.clone();
+    let filters = filter.to_input();
+    paginate(move |offset, limit| {
+        let request = ListAgents::build_query(list_agents::Variables {
+            filters: Some(filters.clone()),
+            pagination: Some(list_agents::OffsetPaginationInput { offset, limit }),
+        });
+        let page = fetch_page::<ListAgents>(client.clone(), request);
+        async move { Ok(page.await?.agents) }
+    })
+}
+
+/// All templates matching `filter`, fetched page by page.
+pub fn list_templates(
+    client: &RekuestClient,
+    filter: TemplateFilter,
+) -> impl Stream<Item = anyhow::Result<AgentTemplate>> {
+    let client = client.clone();
+    let filters = filter.to_input();
+    paginate(move |offset, limit| {
+        let request = ListTemplates::build_query(list_templates::Variables {
+            filters: Some(filters.clone()),
+            pagination: Some(list_templates::OffsetPaginationInput { offset, limit }),
+        });
+        let page = fetch_page::<ListTemplates>(client.clone(), request);
+        async move { Ok(page.await?.templates) }
+    })
+}
+
+/// All provisions matching `filter`, fetched page by page.
+pub fn list_provisions(
+    client: &RekuestClient,
+    filter: ProvisionFilter,
+) -> impl Stream<Item = anyhow::Result<Provision>> {
+    let client = client.clone();
+    let filters = filter.to_input();
+    paginate(move |offset, limit| {
+        let request = ListProvisions::build_query(list_provisions::Variables {
+            filters: Some(filters.clone()),
+            pagination: Some(list_provisions::OffsetPaginationInput { offset, limit }),
+        });
+        let page = fetch_page::<ListProvisions>(client.clone(), request);
+        async move { Ok(page.await?.provisions) }
+    })
+}
+
+/// Pin or unpin the agent with id `agent`. Pinned agents are kept when
+/// cleaning up.
+pub async fn pin_agent(client: &RekuestClient, agent: &str, pin: bool) -> anyhow::Result<()> {
+    let request = PinAgent::build_query(pin_agent::Variables {
+        input: pin_agent::PinInput {
+            id: agent.to_string(),
+            pin,
+        },
+    });
+
+    let response: Response<pin_agent::ResponseData> =
+        client.request(&request).send().await?.json().await?;
+
+    match response.data {
+        Some(_) => Ok(()),
+        None => anyhow::bail!("pinAgent failed: {:?}", response.errors),
+    }
+}
+
+/// Pin or unpin the template with id `template`.
+pub async fn pin_template(client: &RekuestClient, template: &str, pin: bool) -> anyhow::Result<()> {
+    let request = PinTemplate::build_query(pin_template::Variables {
+        input: pin_template::PinInput {
+            id: template.to_string(),
+            pin,
+        },
+    });
+
+    let response: Response<pin_template::ResponseData> =
+        client.request(&request).send().await?.json().await?;
+
+    match response.data {
+        Some(_) => Ok(()),
+        None => anyhow::bail!("pinTemplate failed: {:?}", response.errors),
+    }
+}
+
+/// Delete the agent with id `agent`, with its templates.
+pub async fn delete_agent(client: &RekuestClient, agent: &str) -> anyhow::Result<()> {
+    let request = DeleteAgent::build_query(delete_agent::Variables {
+        input: delete_agent::DeleteAgentInput {
+            id: agent.to_string(),
+        },
+    });
+
+    let response: Response<delete_agent::ResponseData> =
+        client.request(&request).send().await?.json().await?;
+
+    match response.data {
+        Some(_) => Ok(()),
+        None => anyhow::bail!("deleteAgent failed: {:?}", response.errors),
+    }
+}
+
+/// Delete the template with id `template`.
+pub async fn delete_template(client: &RekuestClient, template: &str) -> anyhow::Result<()> {
+    let request = DeleteTemplate::build_query(delete_template::Variables {
+        input: delete_template::DeleteTemplateInput {
+            template: template.to_string(),
+        },
+    });
+
+    let response: Response<delete_template::ResponseData> =
+        client.request(&request).send().await?.json().await?;
+
+    match response.data {
+        Some(_) => Ok(()),
+        None => anyhow::bail!("deleteTemplate failed: {:?}", response.errors),
+    }
+}
+
+/// Ask the agent with id `agent` to reinitialize, or every agent of the
+/// client if `None`.
+pub async fn reinit(client: &RekuestClient, agent: Option<&str>) -> anyhow::Result<()> {
+    let request = Reinit::build_query(reinit::Variables {
+        input: reinit::ReInitInput {
+            agent: agent.map(|agent| agent.to_string()),
+        },
+    });
+
+    let response: Response<reinit::ResponseData> =
+        client.request(&request).send().await?.json().await?;
+
+    match response.data {
+        Some(_) => Ok(()),
+        None => anyhow::bail!("reinit failed: {:?}", response.errors),
+    }
+}
+
+/// Stop the provision with id `provision`.
+pub async fn unprovide(client: &RekuestClient, provision: &str) -> anyhow::Result<()> {
+    let request = Unprovide::build_query(unprovide::Variables {
+        input: unprovide::UnProvideInput {
+            provision: provision.to_string(),
+        },
+    });
+
+    let response: Response<unprovide::ResponseData> =
+        client.request(&request).send().await?.json().await?;
+
+    match response.data {
+        Some(_) => Ok(()),
+        None => anyhow::bail!("unprovide failed: {:?}", response.errors),
+    }
+}
+
+/// Fetch one page of a listing query.
+async fn fetch_page<Q: GraphQLQuery>(
+    client: RekuestClient,
+    request: QueryBody<Q::Variables>,
+) -> anyhow::Result<Q::ResponseData> {
+    let response: Response<Q::ResponseData> = client.request(&request).send().await?.json().await?;
+
+    match response.data {
+        Some(data) => Ok(data),
+        None => anyhow::bail!("{} failed: {:?}", request.operation_name, response.errors),
+    }
+}
+
+/// Stream the items of the pages `fetch(offset, limit)` returns, until a
+/// page comes back short.
+fn paginate<T, F, Fut>(fetch: F) -> impl Stream<Item = anyhow::Result<T>>
+where
+    F: FnMut(i64, i64) -> Fut,
+    Fut: Future<Output = anyhow::Result<Vec<T>>>,
+{
+    let state = (fetch, 0, VecDeque::new(), false);
+    futures::stream::unfold(
+        state,
+        |(mut fetch, mut offset, mut buffer, mut done)| async move {
+            loop {
+                if let Some(item) = buffer.pop_front() {
+                    return Some((Ok(item), (fetch, offset, buffer, done)));
+                }
+                if done {
+                    return None;
+                }
+
+                match fetch(offset, PAGE_SIZE).await {
+                    Ok(page) => {
+                        done = (page.len() as i64) < PAGE_SIZE;
+                        offset += page.len() as i64;
+                        buffer.extend(page);
+                    }
+                    Err(e) => return Some((Err(e), (fetch, offset, buffer, true))),
+                }
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn template_filter_serializes_as_the_schema_expects() {
+        let filter = TemplateFilter {
+            interface: Some("add".to_string()),
+            instance_id: Some("test".to_string()),
+            ..Default::default()
+        };
+        let filters = serde_json::to_value(filter.to_input()).unwrap();
+
+        assert_eq!(filters["interface"]["exact"], json!("add"));
+        assert_eq!(filters["agent"]["instanceId"], json!("test"));
+        assert_eq!(filters["extension"], json!(null));
+        assert_eq!(filters["AND"], json!(null));
+    }
+
+    #[test]
+    fn provision_filter_nests_the_agent_filter() {
+        let filter = ProvisionFilter {
+            status: Some(vec![ProvisionStatus::ACTIVE]),
+            agent: Some(AgentFilter {
+                client_id: Some("app".to_string()),
+                pinned: Some(false),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let filters = serde_json::to_value(filter.to_input()).unwrap();
+
+        assert_eq!(filters["status"], json!(["ACTIVE"]));
+        assert_eq!(filters["agent"]["clientId"], json!("app"));
+        assert_eq!(filters["agent"]["pinned"], json!(false));
+    }
+}
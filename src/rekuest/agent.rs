@@ -1,25 +1,28 @@
-use crate::fakts;
-use crate::App;
-
 use super::agent_protocol::*;
 use super::api::ensure_agent;
 use super::api::get_provision;
 use super::api::EnsureAgent;
 use super::api::GetProvision;
 use super::client::RekuestClient;
+use super::context::Context;
 use super::fakt::RekuestFakt;
-use super::registry::FunctionRegistry;
+use super::hardware::report_hardware;
+use super::registry::{FunctionFuture, FunctionRegistry, ValueMap};
 use futures::{SinkExt, StreamExt};
 use graphql_client::GraphQLQuery;
 use graphql_client::Response;
-use tokio::pin;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
 
+/// Ensure the agent for `instance_id` exists and return it.
 pub async fn create_agent(
     client: &RekuestClient,
     instance_id: &str,
     name: &str,
     extensions: Vec<&str>,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<ensure_agent::EnsureAgentEnsureAgent, Box<dyn std::error::Error>> {
     let request = EnsureAgent::build_query(ensure_agent::Variables {
         input: ensure_agent::AgentInput {
             instance_id: instance_id.to_string(),
@@ -28,17 +31,289 @@ pub async fn create_agent(
         },
     });
 
-    client.request(&request).send().await?;
+    let response: Response<ensure_agent::ResponseData> =
+        client.request(&request).send().await?.json().await?;
 
-    Ok(())
+    match response.data {
+        Some(data) => Ok(data.ensure_agent),
+        None => Err(format!("ensureAgent failed: {:?}", response.errors).into()),
+    }
 }
 
-pub async fn provide_forever(
+/// Connect to the agent websocket and serve assignations from `registry`
+/// until the connection closes. `state` is cloned into every function call.
+pub async fn provide_forever<S: Clone + Send + Sync + 'static>(
     config: RekuestFakt,
     token: String,
-    registry: FunctionRegistry,
-    app: App,
+    registry: FunctionRegistry<S>,
+    state: S,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let client = RekuestClient::new(config.clone(), &token)?;
+    provide(client, config, registry, state).await
+}
+
+/// Like `provide_forever`, but as the agent of `client`, e.g. one created
+/// `with_instance_id`.
+pub async fn provide<S: Clone + Send + Sync + 'static>(
+    client: RekuestClient,
+    config: RekuestFakt,
+    registry: FunctionRegistry<S>,
+    state: S,
 ) -> Result<String, Box<dyn std::error::Error>> {
+    serve(
+        client,
+        config,
+        vec![(None, Box::new(Served { registry, state }))],
+    )
+    .await
+}
+
+/// Builds an agent with its own instance id and any number of extensions,
+/// each with its own registry and state. Several agents, e.g. one per
+/// instrument, can run side by side in one runtime:
+///
+/// ```ignore
+/// let stage = AgentBuilder::new(config.clone(), &token)?
+///     .instance_id("stage")
+///     .extension("default", stage_registry, stage_state);
+/// let camera = AgentBuilder::new(config, &token)?
+///     .instance_id("camera")
+///     .extension("default", camera_registry, camera_state)
+///     .extension("calibration", calibration_registry, calibration_state);
+/// tokio::try_join!(stage.run(), camera.run())?;
+/// ```
+///
+/// A gateway can also stand in for devices that cannot run an agent
+/// themselves, see `foreign`.
+pub struct AgentBuilder {
+    client: RekuestClient,
+    config: RekuestFakt,
+    name: Option<String>,
+    run_cleanup: bool,
+    extensions: Vec<(String, Box<dyn Extension>)>,
+    foreign: Vec<Foreign>,
+}
+
+/// An extension served on behalf of another agent.
+struct Foreign {
+    instance_id: String,
+    extension: String,
+    served: Box<dyn Extension>,
+}
+
+impl AgentBuilder {
+    pub fn new(config: RekuestFakt, token: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self::with_client(
+            RekuestClient::new(config.clone(), token)?,
+            config,
+        ))
+    }
+
+    /// Build the agent of `client`, keeping its instance id.
+    pub fn with_client(client: RekuestClient, config: RekuestFakt) -> Self {
+        Self {
+            client,
+            config,
+            name: None,
+            run_cleanup: true,
+            extensions: Vec::new(),
+            foreign: Vec::new(),
+        }
+    }
+
+    /// Defaults to `DEFAULT_INSTANCE_ID`.
+    pub fn instance_id(mut self, instance_id: &str) -> Self {
+        self.client = self.client.with_instance_id(instance_id);
+        self
+    }
+
+    /// The display name of the agent, defaults to its instance id.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Whether syncing deletes templates an extension no longer registers,
+    /// on by default.
+    pub fn run_cleanup(mut self, run_cleanup: bool) -> Self {
+        self.run_cleanup = run_cleanup;
+        self
+    }
+
+    /// Serve the templates of `registry` under the extension `name`, with
+    /// `state` cloned into every function call.
+    pub fn extension<S: Clone + Send + Sync + 'static>(
+        mut self,
+        name: &str,
+        registry: FunctionRegistry<S>,
+        state: S,
+    ) -> Self {
+        self.extensions
+            .push((name.to_string(), Box::new(Served { registry, state })));
+        self
+    }
+
+    /// Register the templates of `registry` under `extension` on behalf of
+    /// the agent with `instance_id`, e.g. an instrument this agent talks to
+    /// over serial or TCP, and serve their assignations here with `state`.
+    /// The foreign agent is ensured on `run`; its assignations are told
+    /// apart by its agent id.
+    pub fn foreign<S: Clone + Send + Sync + 'static>(
+        mut self,
+        instance_id: &str,
+        extension: &str,
+        registry: FunctionRegistry<S>,
+        state: S,
+    ) -> Self {
+        self.foreign.push(Foreign {
+            instance_id: instance_id.to_string(),
+            extension: extension.to_string(),
+            served: Box::new(Served { registry, state }),
+        });
+        self
+    }
+
+    /// The client of the agent, with its instance id.
+    pub fn client(&self) -> &RekuestClient {
+        &self.client
+    }
+
+    /// Ensure the agent, sync every extension and serve assignations until
+    /// the connection closes.
+    pub async fn run(mut self) -> Result<String, Box<dyn std::error::Error>> {
+        let instance_id = self.client.instance_id().to_string();
+        let name = self.name.unwrap_or_else(|| instance_id.clone());
+        let extensions: Vec<&str> = self.extensions.iter().map(|(n, _)| n.as_str()).collect();
+        let agent = create_agent(&self.client, &instance_id, &name, extensions).await?;
+
+        for (extension, served) in &mut self.extensions {
+            served
+                .sync(&self.client, &agent, extension, self.run_cleanup)
+                .await?;
+        }
+
+        let mut extensions: Vec<_> = self
+            .extensions
+            .into_iter()
+            .map(|(_, served)| (None, served))
+            .collect();
+
+        for mut foreign in self.foreign {
+            let agent = create_agent(
+                &self.client,
+                &foreign.instance_id,
+                &foreign.instance_id,
+                vec![&foreign.extension],
+            )
+            .await?;
+            foreign
+                .served
+                .sync_foreign(&self.client, &agent.id, &foreign.extension)
+                .await?;
+            extensions.push((Some(agent.id), foreign.served));
+        }
+
+        serve(self.client, self.config, extensions).await
+    }
+}
+
+type SyncFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<(), Box<dyn std::error::Error>>> + Send + 'a>>;
+
+/// A registry with the state its functions get, so an agent can serve
+/// extensions with different states.
+trait Extension: Send + Sync {
+    fn sync<'a>(
+        &'a mut self,
+        client: &'a RekuestClient,
+        agent: &'a ensure_agent::EnsureAgentEnsureAgent,
+        extension: &'a str,
+        run_cleanup: bool,
+    ) -> SyncFuture<'a>;
+
+    fn sync_foreign<'a>(
+        &'a mut self,
+        client: &'a RekuestClient,
+        agent: &'a str,
+        extension: &'a str,
+    ) -> SyncFuture<'a>;
+
+    fn provides(&self, template: &str) -> bool;
+
+    /// Start the function for `template`, if this extension provides it.
+    fn start(
+        &self,
+        template: &str,
+        context: ContextParts,
+        args: ValueMap,
+    ) -> Option<FunctionFuture>;
+}
+
+/// Extensions keyed by the id of the foreign agent they serve, or `None`
+/// for the agent's own.
+type Extensions = Vec<(Option<String>, Box<dyn Extension>)>;
+
+/// Everything but the state a `Context` is made of.
+struct ContextParts {
+    client: RekuestClient,
+    assignation: i64,
+    dependencies: HashMap<String, String>,
+    assignment: Assignment,
+    events: tokio::sync::mpsc::Sender<String>,
+}
+
+struct Served<S> {
+    registry: FunctionRegistry<S>,
+    state: S,
+}
+
+impl<S: Clone + Send + Sync + 'static> Extension for Served<S> {
+    fn sync<'a>(
+        &'a mut self,
+        client: &'a RekuestClient,
+        agent: &'a ensure_agent::EnsureAgentEnsureAgent,
+        extension: &'a str,
+        run_cleanup: bool,
+    ) -> SyncFuture<'a> {
+        Box::pin(self.registry.sync(client, agent, extension, run_cleanup))
+    }
+
+    fn sync_foreign<'a>(
+        &'a mut self,
+        client: &'a RekuestClient,
+        agent: &'a str,
+        extension: &'a str,
+    ) -> SyncFuture<'a> {
+        Box::pin(self.registry.sync_foreign(client, agent, extension))
+    }
+
+    fn provides(&self, template: &str) -> bool {
+        self.registry.get_function(template).is_some()
+    }
+
+    fn start(&self, template: &str, parts: ContextParts, args: ValueMap) -> Option<FunctionFuture> {
+        let context = Context::new(
+            self.state.clone(),
+            &parts.assignation.to_string(),
+            parts.client,
+            parts.dependencies,
+        )
+        .with_assignment(parts.assignment)
+        .with_agent_events(parts.events, parts.assignation);
+        self.registry.start(template, context, args)
+    }
+}
+
+/// Connect to the agent websocket of `client` and serve assignations from
+/// `extensions` until the connection closes.
+async fn serve(
+    client: RekuestClient,
+    config: RekuestFakt,
+    extensions: Extensions,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let token = client.token().to_string();
+    let instance_id = client.instance_id().to_string();
+    let hardware = report_hardware(client.clone(), config.hardware.clone());
     let (ws_stream, _) = tokio_tungstenite::connect_async(config.agent.endpoint_url).await?;
     let (write, read) = ws_stream.split();
 
@@ -52,7 +327,7 @@ pub async fn provide_forever(
 
         let init = InitialAgentMessage {
             type_: "INITIAL".to_string(),
-            instance_id: "default".to_string(),
+            instance_id,
             token: token.clone(),
         };
 
@@ -76,6 +351,7 @@ pub async fn provide_forever(
         }
     });
 
+    let extensions = Arc::new(extensions);
     let receive_task = tokio::spawn(async move {
         let mut read = read;
         while let Some(msg) = read.next().await {
@@ -110,58 +386,22 @@ pub async fn provide_forever(
                             provision,
                             args,
                             assignation,
-                            ..
+                            assignment,
                         } => {
                             println!("Received assignment: {}", provision);
 
-                            let get_provision =
-                                GetProvision::build_query(get_provision::Variables {
-                                    id: provision.to_string(),
-                                });
-
-                            let res = app.clone().rekuest.request(&get_provision).send().await;
-
-                            let response_body: Response<get_provision::ResponseData> =
-                                res.unwrap().json().await.unwrap();
-
-                            let template = response_body.data.unwrap().provision.template.id;
-                            match registry.get_function(template.as_str()) {
-                                Some(func) => {
-                                    let returns =
-                                        func((app.clone(), serde_json::to_string(&args).unwrap()));
-                                    pin!(returns);
-
-                                    let x = returns.await;
-
-                                    let event = AssignationEventMessage {
-                                        type_: "ASSIGNATION_EVENT".to_string(),
-                                        assignation: assignation,
-                                        kind: "YIELD".to_string(),
-                                        message: None,
-                                        returns: Some(serde_json::from_str(&x).unwrap()),
-                                    };
-                                    msg_tx
-                                        .send(serde_json::to_string(&event).unwrap())
-                                        .await
-                                        .unwrap();
-
-                                    let event = AssignationEventMessage {
-                                        type_: "ASSIGNATION_EVENT".to_string(),
-                                        assignation: assignation,
-                                        kind: "DONE".to_string(),
-                                        message: None,
-                                        returns: None,
-                                    };
-
-                                    msg_tx
-                                        .send(serde_json::to_string(&event).unwrap())
-                                        .await
-                                        .unwrap();
-                                }
-                                None => {
-                                    println!("Function not found: {}", template);
-                                }
-                            };
+                            // Functions may assign other templates of this
+                            // agent and wait for them, so every assignation
+                            // runs on its own task and the loop keeps reading.
+                            tokio::spawn(run_assignation(
+                                client.clone(),
+                                extensions.clone(),
+                                msg_tx.clone(),
+                                provision,
+                                assignation,
+                                args,
+                                assignment,
+                            ));
                         }
 
                         AgentMessage::Provide { provision } => {
@@ -186,6 +426,144 @@ pub async fn provide_forever(
     });
 
     // Wait for both tasks
-    let _ = tokio::try_join!(queue_task, receive_task)?;
+    let joined = tokio::try_join!(queue_task, receive_task);
+    hardware.abort();
+    joined?;
     Ok("Connection closed".to_string())
 }
+
+/// Run the function the assignation of `provision` is for and send its
+/// events through `messages`.
+async fn run_assignation(
+    client: RekuestClient,
+    extensions: Arc<Extensions>,
+    messages: tokio::sync::mpsc::Sender<String>,
+    provision: i64,
+    assignation: i64,
+    args: ValueMap,
+    assignment: Assignment,
+) {
+    let provision = match fetch_provision(&client, provision).await {
+        Ok(provision) => provision,
+        Err(e) => {
+            let message = format!("Failed to look up provision {}: {}", provision, e);
+            println!("{}", message);
+            send_events(&messages, vec![error_event(assignation, message)]).await;
+            return;
+        }
+    };
+    let template = provision.template.id;
+
+    // Assignations of foreign templates go to the extensions registered for
+    // that agent.
+    let agent = Some(provision.agent.id)
+        .filter(|agent| extensions.iter().any(|(a, _)| a.as_ref() == Some(agent)));
+
+    // Reservations made for the template's dependencies, keyed by reference
+    // (or hash if none was given).
+    let dependencies: HashMap<String, String> = provision
+        .caused_reservations
+        .into_iter()
+        .filter_map(|reservation| {
+            let dependency = reservation.causing_dependency?;
+            let key = dependency.reference.unwrap_or(dependency.hash);
+            Some((key, reservation.id))
+        })
+        .collect();
+
+    let parts = ContextParts {
+        client: client.clone(),
+        assignation,
+        dependencies,
+        assignment,
+        events: messages.clone(),
+    };
+    let returns = match extensions
+        .iter()
+        .filter(|(a, _)| *a == agent)
+        .map(|(_, extension)| extension)
+        .find(|extension| extension.provides(&template))
+        .and_then(|extension| extension.start(&template, parts, args))
+    {
+        Some(returns) => returns,
+        None => {
+            let message = format!("Function not found: {}", template);
+            println!("{}", message);
+            send_events(&messages, vec![error_event(assignation, message)]).await;
+            return;
+        }
+    };
+
+    let events = match returns.await {
+        Ok(returns) => vec![
+            AssignationEventMessage {
+                type_: "ASSIGNATION_EVENT".to_string(),
+                assignation,
+                kind: "YIELD".to_string(),
+                message: None,
+                returns: Some(returns),
+                progress: None,
+            },
+            AssignationEventMessage {
+                type_: "ASSIGNATION_EVENT".to_string(),
+                assignation,
+                kind: "DONE".to_string(),
+                message: None,
+                returns: None,
+                progress: None,
+            },
+        ],
+        Err(e) => vec![error_event(assignation, e.to_string())],
+    };
+    send_events(&messages, events).await;
+}
+
+/// Look up the template and agent `provision` is for, and the reservations
+/// made for the template's dependencies.
+async fn fetch_provision(
+    client: &RekuestClient,
+    provision: i64,
+) -> Result<get_provision::GetProvisionProvision, Box<dyn std::error::Error + Send + Sync>> {
+    let request = GetProvision::build_query(get_provision::Variables {
+        id: provision.to_string(),
+    });
+
+    let response: Response<get_provision::ResponseData> =
+        client.request(&request).send().await?.json().await?;
+
+    match response.data {
+        Some(data) => Ok(data.provision),
+        None => Err(format!("provision failed: {:?}", response.errors).into()),
+    }
+}
+
+fn error_event(assignation: i64, message: String) -> AssignationEventMessage {
+    AssignationEventMessage {
+        type_: "ASSIGNATION_EVENT".to_string(),
+        assignation,
+        kind: "ERROR".to_string(),
+        message: Some(message),
+        returns: None,
+        progress: None,
+    }
+}
+
+async fn send_events(
+    messages: &tokio::sync::mpsc::Sender<String>,
+    events: Vec<AssignationEventMessage>,
+) {
+    for event in events {
+        let assignation = event.assignation;
+        if messages
+            .send(serde_json::to_string(&event).unwrap())
+            .await
+            .is_err()
+        {
+            println!(
+                "Lost the connection before assignation {} finished",
+                assignation
+            );
+            return;
+        }
+    }
+}
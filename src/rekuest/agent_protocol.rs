@@ -1,6 +1,4 @@
-use std::collections::HashMap;
-
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct InitialAgentMessage {
@@ -17,7 +15,10 @@ pub struct AssignationEventMessage {
     pub assignation: i64,
     pub kind: String,
     pub message: Option<String>,
-    pub returns: Option<HashMap<String, serde_json::Value>>,
+    pub returns: Option<serde_json::Map<String, serde_json::Value>>,
+    /// Percent done, for `PROGRESS` events.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<i64>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -36,6 +37,49 @@ pub struct Inquiry {
     pub id: String,
 }
 
+/// What an `ASSIGN` message tells about the assignation besides its
+/// arguments, as passed to `AssignInput`.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Assignment {
+    /// The caller's reference, to tell its assignations apart.
+    #[serde(default)]
+    pub reference: Option<String>,
+    /// The assignation that caused this one, e.g. through a dependency.
+    #[serde(default, deserialize_with = "lenient_id")]
+    pub parent: Option<String>,
+    #[serde(default, deserialize_with = "lenient_id")]
+    pub reservation: Option<String>,
+    /// The user the assignation runs for.
+    #[serde(default, deserialize_with = "lenient_id")]
+    pub user: Option<String>,
+    /// The caller accepts a cached result.
+    #[serde(default)]
+    pub cached: bool,
+    /// Passed through as sent; `Context::log` reports either way.
+    #[serde(default)]
+    pub log: bool,
+    /// The result is only used once and should not be kept.
+    #[serde(default)]
+    pub ephemeral: bool,
+    #[serde(default)]
+    pub is_hook: bool,
+}
+
+/// Ids are sent as strings or numbers, depending on the server version.
+fn lenient_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Id {
+        String(String),
+        Number(i64),
+    }
+
+    Ok(Option::<Id>::deserialize(deserializer)?.map(|id| match id {
+        Id::String(id) => id,
+        Id::Number(id) => id.to_string(),
+    }))
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 #[serde(tag = "type")]
 pub enum AgentMessage {
@@ -52,8 +96,10 @@ pub enum AgentMessage {
     #[serde(rename = "ASSIGN")]
     Assign {
         assignation: i64,
-        args: HashMap<String, serde_json::Value>,
+        args: serde_json::Map<String, serde_json::Value>,
         provision: i64,
+        #[serde(flatten)]
+        assignment: Assignment,
     },
     #[serde(rename = "PROVIDE")]
     Provide { provision: i64 },
@@ -3,9 +3,11 @@ use graphql_client::GraphQLQuery;
 type InstanceId = String;
 type SearchQuery = String;
 type ValidatorFunction = String;
-type AnyDefault = String;
+type AnyDefault = serde_json::Value;
 type NodeHash = String;
 type Identifier = String;
+type Args = serde_json::Map<String, serde_json::Value>;
+type DateTime = String;
 
 // The paths are relative to the directory where your `Cargo.toml` is located.
 // Both json and the GraphQL schema language are supported as sources for the schema
@@ -36,3 +38,313 @@ pub struct CreateTemplate;
     derives = "Clone"
 )]
 pub struct GetProvision;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/assign.graphql",
+    response_derives = "Debug,Clone",
+    variables_derives = "Clone",
+    derives = "Clone"
+)]
+pub struct Assign;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/agent_templates.graphql",
+    response_derives = "Debug,Clone,Serialize"
+)]
+pub struct AgentTemplates;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/set_extension_templates.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct SetExtensionTemplates;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/reserve.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct Reserve;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/unreserve.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct Unreserve;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/cancel.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct Cancel;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/assignation_events.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct AssignationEvents;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/reservation_events.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct ReservationEvents;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/create_state_schema.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct CreateStateSchema;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/set_state.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct SetState;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/update_state.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct UpdateState;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/archive_state.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct ArchiveState;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/get_state.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct GetState;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/state_update_events.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct StateUpdateEvents;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/create_dashboard.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct CreateDashboard;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/create_panel.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct CreatePanel;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/dashboards.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct Dashboards;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/create_hardware_record.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct CreateHardwareRecord;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/create_test_case.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct CreateTestCase;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/create_test_result.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct CreateTestResult;
+
+/// Variables of `SetExtensionTemplates` expressed with the `create_template`
+/// input types, so templates built with `Definition` and `Port` can be sent
+/// through either mutation.
+pub mod set_extension_templates_vars {
+    use super::create_template::TemplateInput;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct SetExtensionTemplatesInput {
+        pub templates: Vec<TemplateInput>,
+        pub instance_id: String,
+        pub extension: String,
+        pub run_cleanup: bool,
+    }
+
+    #[derive(Serialize)]
+    pub struct Variables {
+        pub input: SetExtensionTemplatesInput,
+    }
+}
+
+/// Variables of `CreateForeignTemplate` expressed with the
+/// `create_template` input types.
+pub mod create_foreign_template_vars {
+    use super::create_template::TemplateInput;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    pub struct CreateForeignTemplateInput {
+        pub agent: String,
+        pub template: TemplateInput,
+        pub extension: String,
+    }
+
+    #[derive(Serialize)]
+    pub struct Variables {
+        pub input: CreateForeignTemplateInput,
+    }
+}
+
+/// Variables of `CreateStateSchema` expressed with the `create_template`
+/// input types, so state schemas can be described with `Port`.
+pub mod create_state_schema_vars {
+    use super::create_template::PortInput;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    pub struct StateSchemaInput {
+        pub ports: Vec<PortInput>,
+        pub name: String,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct CreateStateSchemaInput {
+        pub state_schema: StateSchemaInput,
+    }
+
+    #[derive(Serialize)]
+    pub struct Variables {
+        pub input: CreateStateSchemaInput,
+    }
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/list_agents.graphql",
+    response_derives = "Debug,Clone",
+    variables_derives = "Debug,Clone,Default"
+)]
+pub struct ListAgents;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/list_templates.graphql",
+    response_derives = "Debug,Clone",
+    variables_derives = "Debug,Clone"
+)]
+pub struct ListTemplates;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/list_provisions.graphql",
+    response_derives = "Debug,Clone",
+    variables_derives = "Debug,Clone,Default"
+)]
+pub struct ListProvisions;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/pin_agent.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct PinAgent;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/pin_template.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct PinTemplate;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/delete_agent.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct DeleteAgent;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/delete_template.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct DeleteTemplate;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/reinit.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct Reinit;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/unprovide.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct Unprovide;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/rekuest/schema.graphql",
+    query_path = "graphql/rekuest/create_foreign_template.graphql",
+    response_derives = "Debug,Clone"
+)]
+pub struct CreateForeignTemplate;
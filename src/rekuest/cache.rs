@@ -0,0 +1,158 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use sha2::{Digest, Sha256};
+
+use super::hash::canonical_json;
+use super::registry::ValueMap;
+
+/// Returns of a pure function, keyed by template and arguments. Enabled per
+/// template with `FunctionRegistry::cache_results`.
+///
+/// Once `capacity` results are stored, the oldest one is dropped.
+pub struct ResultCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+struct Entries {
+    returns: HashMap<String, ValueMap>,
+    /// Keys in insertion order, oldest first.
+    order: VecDeque<String>,
+}
+
+impl ResultCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(Entries {
+                returns: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// The key of a call of `interface` with `args`. Arguments are
+    /// canonicalized first, so the key does not depend on their order.
+    pub fn key(interface: &str, args: &ValueMap) -> String {
+        let canonical = canonical_json(&serde_json::Value::Object(args.clone()));
+        Sha256::digest(format!("{}\0{}", interface, canonical).as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    pub fn get(&self, key: &str) -> Option<ValueMap> {
+        self.entries.lock().unwrap().returns.get(key).cloned()
+    }
+
+    pub fn insert(&self, key: String, returns: ValueMap) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.returns.insert(key.clone(), returns).is_none() {
+            entries.order.push_back(key);
+        }
+        while entries.order.len() > self.capacity {
+            if let Some(oldest) = entries.order.pop_front() {
+                entries.returns.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().returns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.returns.clear();
+        entries.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn map(value: serde_json::Value) -> ValueMap {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn hit_and_miss() {
+        let cache = ResultCache::new(2);
+        let key = ResultCache::key("add", &map(json!({ "a": 1, "b": 2 })));
+        assert_eq!(cache.get(&key), None);
+
+        cache.insert(key.clone(), map(json!({ "sum": 3 })));
+        assert_eq!(cache.get(&key), Some(map(json!({ "sum": 3 }))));
+        let other = ResultCache::key("add", &map(json!({ "a": 1, "b": 3 })));
+        assert_eq!(cache.get(&other), None);
+    }
+
+    #[test]
+    fn evicts_oldest_first() {
+        let cache = ResultCache::new(2);
+        cache.insert("a".to_string(), map(json!({ "v": 1 })));
+        cache.insert("b".to_string(), map(json!({ "v": 2 })));
+        // Replacing an entry does not make it younger.
+        cache.insert("a".to_string(), map(json!({ "v": 3 })));
+        cache.insert("c".to_string(), map(json!({ "v": 4 })));
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.get("b"), Some(map(json!({ "v": 2 }))));
+        assert_eq!(cache.get("c"), Some(map(json!({ "v": 4 }))));
+    }
+
+    #[test]
+    fn zero_capacity_stores_nothing() {
+        let cache = ResultCache::new(0);
+        cache.insert("a".to_string(), map(json!({ "v": 1 })));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn clear_drops_everything() {
+        let cache = ResultCache::new(2);
+        cache.insert("a".to_string(), map(json!({ "v": 1 })));
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.get("a"), None);
+    }
+
+    #[test]
+    fn key_ignores_argument_order() {
+        let nested = |inner: serde_json::Value| json!({ "x": 1, "options": inner });
+        assert_eq!(
+            ResultCache::key("f", &map(json!({ "a": 1, "b": [1, 2] }))),
+            ResultCache::key("f", &map(json!({ "b": [1, 2], "a": 1 }))),
+        );
+        assert_eq!(
+            ResultCache::key("f", &map(nested(json!({ "p": 1, "q": 2 })))),
+            ResultCache::key("f", &map(nested(json!({ "q": 2, "p": 1 })))),
+        );
+    }
+
+    #[test]
+    fn key_depends_on_interface_and_values() {
+        let args = map(json!({ "a": 1 }));
+        assert_ne!(ResultCache::key("f", &args), ResultCache::key("g", &args));
+        assert_ne!(
+            ResultCache::key("f", &args),
+            ResultCache::key("f", &map(json!({ "a": 2 }))),
+        );
+        // List order is part of the value.
+        assert_ne!(
+            ResultCache::key("f", &map(json!({ "a": [1, 2] }))),
+            ResultCache::key("f", &map(json!({ "a": [2, 1] }))),
+        );
+    }
+}
@@ -0,0 +1,403 @@
+use std::fmt;
+use std::time::Duration;
+
+use graphql_client::GraphQLQuery;
+use graphql_client::Response;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::time::Instant;
+
+use super::api::assign;
+use super::api::assignation_events;
+use super::api::assignation_events::AssignationEventKind;
+use super::api::cancel;
+use super::api::reserve;
+use super::api::unreserve;
+use super::api::Assign;
+use super::api::AssignationEvents;
+use super::api::Cancel;
+use super::api::Reserve;
+use super::api::Unreserve;
+use super::client::RekuestClient;
+use super::registry::ValueMap;
+use super::subscription::subscribe;
+use super::subscription::Subscription;
+
+/// What to call.
+#[derive(Debug, Clone)]
+pub enum Target {
+    /// Any implementation of the node with this hash. A reservation is made
+    /// for the call and released once it finishes.
+    Hash(String),
+    /// A specific template.
+    Template(String),
+    /// An existing reservation.
+    Reservation(String),
+}
+
+impl Target {
+    pub fn hash(hash: &str) -> Self {
+        Target::Hash(hash.to_string())
+    }
+
+    pub fn template(template: &str) -> Self {
+        Target::Template(template.to_string())
+    }
+
+    pub fn reservation(reservation: &str) -> Self {
+        Target::Reservation(reservation.to_string())
+    }
+}
+
+/// Why a call did not produce returns.
+#[derive(Debug)]
+pub enum CallError {
+    /// The arguments did not serialize into a map of port keys.
+    InvalidArgs(String),
+    /// A request to rekuest failed, or rekuest rejected it.
+    Request(String),
+    /// The called function reported an error.
+    Failed(String),
+    Cancelled,
+    Interrupted,
+    /// The call did not finish within its timeout. It is cancelled.
+    Timeout,
+    /// The event stream ended before the call finished.
+    Disconnected,
+    /// The returns did not deserialize into the requested type.
+    InvalidReturns(String),
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallError::InvalidArgs(message) => write!(f, "Invalid arguments: {}", message),
+            CallError::Request(message) => write!(f, "Request failed: {}", message),
+            CallError::Failed(message) => write!(f, "Call failed: {}", message),
+            CallError::Cancelled => write!(f, "Call was cancelled"),
+            CallError::Interrupted => write!(f, "Call was interrupted"),
+            CallError::Timeout => write!(f, "Call timed out"),
+            CallError::Disconnected => write!(f, "Lost the connection before the call finished"),
+            CallError::InvalidReturns(message) => write!(f, "Invalid returns: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for CallError {}
+
+impl From<reqwest::Error> for CallError {
+    fn from(e: reqwest::Error) -> Self {
+        CallError::Request(e.to_string())
+    }
+}
+
+/// An intermediate event of a running call.
+#[derive(Debug, Clone)]
+pub enum CallEvent {
+    Yield(ValueMap),
+    Log {
+        level: Option<String>,
+        message: String,
+    },
+    Progress {
+        progress: Option<i64>,
+        message: Option<String>,
+    },
+}
+
+/// A running assignation. Dropping it before it finished cancels it.
+pub struct Call {
+    client: RekuestClient,
+    assignation: String,
+    /// A reservation made only for this call, released on drop.
+    reservation: Option<String>,
+    events: Subscription<AssignationEvents>,
+    deadline: Option<Instant>,
+    finished: bool,
+}
+
+impl RekuestClient {
+    /// Assign `args` to `target` and return the running `Call`.
+    pub async fn call<A: Serialize>(&self, target: Target, args: &A) -> Result<Call, CallError> {
+        start_call(self, target, to_args(args)?, None).await
+    }
+}
+
+impl Call {
+    /// The id of the assignation.
+    pub fn id(&self) -> &str {
+        &self.assignation
+    }
+
+    /// Give up (and cancel) if the call has not finished within `timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Some(Instant::now() + timeout);
+        self
+    }
+
+    /// Wait for the next `YIELD`, `LOG` or `PROGRESS` event. Returns `None`
+    /// once the call is done; failures are returned as the last event.
+    pub async fn next_event(&mut self) -> Option<Result<CallEvent, CallError>> {
+        if self.finished {
+            return None;
+        }
+
+        loop {
+            let next = match self.deadline {
+                Some(deadline) => match tokio::time::timeout_at(deadline, self.events.next()).await
+                {
+                    Ok(next) => next,
+                    Err(_) => return Some(Err(CallError::Timeout)),
+                },
+                None => self.events.next().await,
+            };
+
+            let event = match next {
+                Some(Ok(data)) => data.assignation_events,
+                Some(Err(e)) => return Some(Err(CallError::Request(e.to_string()))),
+                None => return Some(Err(CallError::Disconnected)),
+            };
+            if event.assignation.id != self.assignation {
+                continue;
+            }
+
+            let message = event.message.unwrap_or_default();
+            match event.kind {
+                AssignationEventKind::YIELD => {
+                    return Some(match event.returns {
+                        Some(serde_json::Value::Object(returns)) => Ok(CallEvent::Yield(returns)),
+                        None | Some(serde_json::Value::Null) => {
+                            Ok(CallEvent::Yield(ValueMap::new()))
+                        }
+                        Some(other) => Err(CallError::InvalidReturns(format!(
+                            "expected a map of port keys, got {}",
+                            other
+                        ))),
+                    });
+                }
+                AssignationEventKind::LOG => {
+                    return Some(Ok(CallEvent::Log {
+                        level: event.level.map(|level| format!("{:?}", level)),
+                        message,
+                    }));
+                }
+                AssignationEventKind::PROGRESS => {
+                    return Some(Ok(CallEvent::Progress {
+                        progress: event.progress,
+                        message: Some(message).filter(|m| !m.is_empty()),
+                    }));
+                }
+                AssignationEventKind::DONE => {
+                    self.finished = true;
+                    return None;
+                }
+                AssignationEventKind::ERROR | AssignationEventKind::CRITICAL => {
+                    self.finished = true;
+                    return Some(Err(CallError::Failed(message)));
+                }
+                AssignationEventKind::CANCELLED => {
+                    self.finished = true;
+                    return Some(Err(CallError::Cancelled));
+                }
+                AssignationEventKind::INTERUPTED => {
+                    self.finished = true;
+                    return Some(Err(CallError::Interrupted));
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    /// Wait for the call to finish and deserialize its last yield into `R`.
+    pub async fn returns<R: DeserializeOwned>(mut self) -> Result<R, CallError> {
+        let mut returns = serde_json::Value::Null;
+        while let Some(event) = self.next_event().await {
+            if let CallEvent::Yield(map) = event? {
+                returns = serde_json::Value::Object(map);
+            }
+        }
+
+        serde_json::from_value(returns).map_err(|e| CallError::InvalidReturns(e.to_string()))
+    }
+}
+
+impl Drop for Call {
+    fn drop(&mut self) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let client = self.client.clone();
+        let assignation = (!self.finished).then(|| self.assignation.clone());
+        let reservation = self.reservation.take();
+
+        runtime.spawn(async move {
+            if let Some(assignation) = assignation {
+                if let Err(e) = cancel_assignation(&client, &assignation).await {
+                    println!("Failed to cancel assignation {}: {}", assignation, e);
+                }
+            }
+            if let Some(reservation) = reservation {
+                if let Err(e) = unreserve_reservation(&client, &reservation).await {
+                    println!("Failed to unreserve {}: {}", reservation, e);
+                }
+            }
+        });
+    }
+}
+
+/// Serialize `args` into the map of port keys an assignation expects.
+pub(crate) fn to_args<A: Serialize>(args: &A) -> Result<ValueMap, CallError> {
+    match serde_json::to_value(args) {
+        Ok(serde_json::Value::Object(args)) => Ok(args),
+        Ok(serde_json::Value::Null) => Ok(ValueMap::new()),
+        Ok(other) => Err(CallError::InvalidArgs(format!(
+            "expected a map of port keys, got {}",
+            other
+        ))),
+        Err(e) => Err(CallError::InvalidArgs(e.to_string())),
+    }
+}
+
+/// Subscribe to assignation events, then assign, so no event is missed.
+/// With a `parent` the call is made on behalf of that assignation.
+pub(crate) async fn start_call(
+    client: &RekuestClient,
+    target: Target,
+    args: ValueMap,
+    parent: Option<String>,
+) -> Result<Call, CallError> {
+    let events = subscribe::<AssignationEvents>(
+        client,
+        assignation_events::Variables {
+            instance_id: client.instance_id().to_string(),
+        },
+    )
+    .await
+    .map_err(|e| CallError::Request(e.to_string()))?;
+
+    let (template, reservation, owned_reservation) = match target {
+        Target::Hash(hash) => {
+            let reservation = reserve_hash(client, &hash, parent.clone()).await?;
+            (None, Some(reservation.clone()), Some(reservation))
+        }
+        Target::Template(template) => (Some(template), None, None),
+        Target::Reservation(reservation) => (None, Some(reservation), None),
+    };
+
+    let input = assign::AssignInput {
+        instance_id: client.instance_id().to_string(),
+        node: None,
+        template,
+        reservation,
+        hooks: None,
+        args,
+        reference: None,
+        parent,
+        cached: false,
+        log: true,
+        ephemeral: false,
+        is_hook: false,
+    };
+
+    let assignation = match assign_input(client, input).await {
+        Ok(assignation) => assignation,
+        Err(e) => {
+            if let Some(reservation) = owned_reservation {
+                let _ = unreserve_reservation(client, &reservation).await;
+            }
+            return Err(e);
+        }
+    };
+
+    Ok(Call {
+        client: client.clone(),
+        assignation,
+        reservation: owned_reservation,
+        events,
+        deadline: None,
+        finished: false,
+    })
+}
+
+async fn assign_input(
+    client: &RekuestClient,
+    input: assign::AssignInput,
+) -> Result<String, CallError> {
+    let request = Assign::build_query(assign::Variables { input });
+
+    let response: Response<assign::ResponseData> =
+        client.request(&request).send().await?.json().await?;
+
+    match response.data {
+        Some(data) => Ok(data.assign.id),
+        None => Err(CallError::Request(format!(
+            "assign failed: {:?}",
+            response.errors
+        ))),
+    }
+}
+
+async fn reserve_hash(
+    client: &RekuestClient,
+    hash: &str,
+    parent: Option<String>,
+) -> Result<String, CallError> {
+    let request = Reserve::build_query(reserve::Variables {
+        input: reserve::ReserveInput {
+            assignation_id: parent,
+            instance_id: client.instance_id().to_string(),
+            node: None,
+            template: None,
+            title: None,
+            hash: Some(hash.to_string()),
+            reference: None,
+            binds: None,
+        },
+    });
+
+    let response: Response<reserve::ResponseData> =
+        client.request(&request).send().await?.json().await?;
+
+    match response.data {
+        Some(data) => Ok(data.reserve.id),
+        None => Err(CallError::Request(format!(
+            "reserve failed: {:?}",
+            response.errors
+        ))),
+    }
+}
+
+async fn cancel_assignation(client: &RekuestClient, assignation: &str) -> anyhow::Result<()> {
+    let request = Cancel::build_query(cancel::Variables {
+        input: cancel::CancelInput {
+            assignation: assignation.to_string(),
+        },
+    });
+
+    let response: Response<cancel::ResponseData> =
+        client.request(&request).send().await?.json().await?;
+
+    match response.data {
+        Some(_) => Ok(()),
+        None => anyhow::bail!("cancel failed: {:?}", response.errors),
+    }
+}
+
+pub(crate) async fn unreserve_reservation(
+    client: &RekuestClient,
+    reservation: &str,
+) -> anyhow::Result<()> {
+    let request = Unreserve::build_query(unreserve::Variables {
+        input: unreserve::UnreserveInput {
+            reservation: reservation.to_string(),
+        },
+    });
+
+    let response: Response<unreserve::ResponseData> =
+        client.request(&request).send().await?.json().await?;
+
+    match response.data {
+        Some(_) => Ok(()),
+        None => anyhow::bail!("unreserve failed: {:?}", response.errors),
+    }
+}
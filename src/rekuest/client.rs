@@ -6,9 +6,15 @@ use super::fakt::RekuestFakt;
 
 pub type RekuestClientFunc = reqwest::RequestBuilder;
 
+/// The instance id used unless a different one is configured.
+pub const DEFAULT_INSTANCE_ID: &str = "default";
+
 pub struct RekuestClient {
     client: Client,
     endpoint_url: String,
+    ws_endpoint_url: String,
+    token: String,
+    instance_id: String,
 }
 
 impl RekuestClient {
@@ -24,12 +30,42 @@ impl RekuestClient {
             )
             .build()?;
 
+        let ws_endpoint_url = match fakt.ws_endpoint_url {
+            Some(url) => url,
+            None => fakt
+                .endpoint_url
+                .replacen("https://", "wss://", 1)
+                .replacen("http://", "ws://", 1),
+        };
+
         Ok(Self {
             client,
             endpoint_url: fakt.endpoint_url.clone(),
+            ws_endpoint_url,
+            token: token.to_string(),
+            instance_id: DEFAULT_INSTANCE_ID.to_string(),
         })
     }
 
+    /// Use `instance_id` for assignations and subscriptions made through
+    /// this client.
+    pub fn with_instance_id(mut self, instance_id: &str) -> Self {
+        self.instance_id = instance_id.to_string();
+        self
+    }
+
+    pub fn instance_id(&self) -> &str {
+        &self.instance_id
+    }
+
+    pub fn ws_endpoint_url(&self) -> &str {
+        &self.ws_endpoint_url
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
     pub fn request<T: serde::Serialize>(&self, body: &QueryBody<T>) -> RekuestClientFunc {
         self.client.post(&self.endpoint_url).json(body)
     }
@@ -40,6 +76,9 @@ impl Clone for RekuestClient {
         Self {
             client: self.client.clone(),
             endpoint_url: self.endpoint_url.clone(),
+            ws_endpoint_url: self.ws_endpoint_url.clone(),
+            token: self.token.clone(),
+            instance_id: self.instance_id.clone(),
         }
     }
 }
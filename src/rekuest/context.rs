@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+
+use graphql_client::GraphQLQuery;
+use graphql_client::Response;
+use serde::Serialize;
+
+use super::agent_protocol::AssignationEventMessage;
+use super::agent_protocol::Assignment;
+use super::api::assign;
+use super::api::Assign;
+use super::call::start_call;
+use super::call::to_args;
+use super::call::Call;
+use super::call::CallError;
+use super::call::Target;
+use super::client::RekuestClient;
+use super::registry::ValueMap;
+
+/// Everything a registered function gets besides its arguments: the
+/// application state and a handle on the assignation it is running for.
+pub struct Context<S> {
+    state: S,
+    assignation: String,
+    /// `None` when running locally, without a server.
+    client: Option<RekuestClient>,
+    /// Reservation ids of the provision's dependencies, keyed by reference.
+    dependencies: HashMap<String, String>,
+    assignment: Assignment,
+    events: EventSink,
+}
+
+/// Where `Context::log` and `Context::progress` go.
+enum EventSink {
+    /// Printed to the terminal.
+    Terminal,
+    /// Sent over the agent websocket, as events of `assignation`.
+    Agent {
+        messages: tokio::sync::mpsc::Sender<String>,
+        assignation: i64,
+    },
+}
+
+impl<S> Context<S> {
+    pub fn new(
+        state: S,
+        assignation: &str,
+        client: RekuestClient,
+        dependencies: HashMap<String, String>,
+    ) -> Self {
+        Self {
+            state,
+            assignation: assignation.to_string(),
+            client: Some(client),
+            dependencies,
+            assignment: Assignment::default(),
+            events: EventSink::Terminal,
+        }
+    }
+
+    /// A context for running a function locally: there is no server, so
+    /// there are no dependencies and logs and progress are printed.
+    pub fn local(state: S) -> Self {
+        Self {
+            state,
+            assignation: "local".to_string(),
+            client: None,
+            dependencies: HashMap::new(),
+            assignment: Assignment::default(),
+            events: EventSink::Terminal,
+        }
+    }
+
+    /// Send logs and progress over the agent websocket.
+    pub(crate) fn with_agent_events(
+        mut self,
+        messages: tokio::sync::mpsc::Sender<String>,
+        assignation: i64,
+    ) -> Self {
+        self.events = EventSink::Agent {
+            messages,
+            assignation,
+        };
+        self
+    }
+
+    /// Attach the payload of the `ASSIGN` message this call serves.
+    pub(crate) fn with_assignment(mut self, assignment: Assignment) -> Self {
+        self.assignment = assignment;
+        self
+    }
+
+    /// The application state the agent was started with.
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// The client of the agent, e.g. to call other nodes. `None` when
+    /// running locally.
+    pub fn client(&self) -> Option<&RekuestClient> {
+        self.client.as_ref()
+    }
+
+    /// Report a log message for the running assignation.
+    pub async fn log(&self, message: &str) {
+        self.event("LOG", Some(message), None).await;
+    }
+
+    /// Report how far the running assignation got, in percent.
+    pub async fn progress(&self, progress: i64, message: Option<&str>) {
+        self.event("PROGRESS", message, Some(progress)).await;
+    }
+
+    async fn event(&self, kind: &str, message: Option<&str>, progress: Option<i64>) {
+        match &self.events {
+            EventSink::Terminal => match (progress, message) {
+                (Some(progress), Some(message)) => println!("[{:>3}%] {}", progress, message),
+                (Some(progress), None) => println!("[{:>3}%]", progress),
+                (None, message) => println!("{}", message.unwrap_or_default()),
+            },
+            EventSink::Agent {
+                messages,
+                assignation,
+            } => {
+                let event = AssignationEventMessage {
+                    type_: "ASSIGNATION_EVENT".to_string(),
+                    assignation: *assignation,
+                    kind: kind.to_string(),
+                    message: message.map(|m| m.to_string()),
+                    returns: None,
+                    progress,
+                };
+                if let Ok(event) = serde_json::to_string(&event) {
+                    if messages.send(event).await.is_err() {
+                        println!("Dropped {} event, the agent is disconnected", kind);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The id of the assignation this call serves.
+    pub fn assignation(&self) -> &str {
+        &self.assignation
+    }
+
+    /// How the assignation was requested: reference, parent, user and the
+    /// `cached` and `ephemeral` flags. All unset when running locally.
+    pub fn assignment(&self) -> &Assignment {
+        &self.assignment
+    }
+
+    /// The dependency declared with `reference` (or, without a reference,
+    /// with this hash), if the server reserved it for this provision.
+    pub fn dependency(&self, reference: &str) -> Option<DependencyHandle> {
+        let reservation = self.dependencies.get(reference)?;
+        Some(DependencyHandle {
+            client: self.client.clone()?,
+            reservation: reservation.clone(),
+            parent: self.assignation.clone(),
+        })
+    }
+}
+
+/// A reserved dependency of the running function.
+pub struct DependencyHandle {
+    client: RekuestClient,
+    reservation: String,
+    parent: String,
+}
+
+impl DependencyHandle {
+    /// The id of the reservation the server created for this dependency.
+    pub fn reservation(&self) -> &str {
+        &self.reservation
+    }
+
+    /// Assign `args` to the dependency, as a child of the running
+    /// assignation. Returns the id of the new assignation.
+    pub async fn assign<A: Serialize>(&self, args: &A) -> anyhow::Result<String> {
+        self.assign_map(to_args(args)?).await
+    }
+
+    /// Like `assign`, but follow the new assignation to its returns.
+    pub async fn call<A: Serialize>(&self, args: &A) -> Result<Call, CallError> {
+        start_call(
+            &self.client,
+            Target::reservation(&self.reservation),
+            to_args(args)?,
+            Some(self.parent.clone()),
+        )
+        .await
+    }
+
+    async fn assign_map(&self, args: ValueMap) -> anyhow::Result<String> {
+        let request = Assign::build_query(assign::Variables {
+            input: assign::AssignInput {
+                instance_id: self.client.instance_id().to_string(),
+                node: None,
+                template: None,
+                reservation: Some(self.reservation.clone()),
+                hooks: None,
+                args,
+                reference: None,
+                parent: Some(self.parent.clone()),
+                cached: false,
+                log: false,
+                ephemeral: false,
+                is_hook: false,
+            },
+        });
+
+        let response: Response<assign::ResponseData> =
+            self.client.request(&request).send().await?.json().await?;
+
+        match response.data {
+            Some(data) => Ok(data.assign.id),
+            None => anyhow::bail!("assign failed: {:?}", response.errors),
+        }
+    }
+}
@@ -0,0 +1,341 @@
+use graphql_client::GraphQLQuery;
+use graphql_client::Response;
+
+use super::api::create_dashboard;
+use super::api::create_dashboard::{UIChildInput, UIChildKind, UITreeInput};
+use super::api::create_panel;
+use super::api::create_panel::PanelKind;
+use super::api::dashboards;
+use super::api::ensure_agent::EnsureAgentEnsureAgent;
+use super::api::CreateDashboard;
+use super::api::CreatePanel;
+use super::api::Dashboards;
+use super::client::RekuestClient;
+use super::registry::ValueMap;
+
+/// A node of a dashboard's UI tree.
+pub struct UiChild {
+    kind: UIChildKind,
+    state: Option<String>,
+    hidden: Option<bool>,
+    children: Vec<UiChild>,
+    left: Option<Box<UiChild>>,
+    right: Option<Box<UiChild>>,
+}
+
+impl UiChild {
+    fn with_kind(kind: UIChildKind) -> Self {
+        Self {
+            kind,
+            state: None,
+            hidden: None,
+            children: Vec::new(),
+            left: None,
+            right: None,
+        }
+    }
+
+    /// Show `left` and `right` side by side.
+    pub fn split(left: UiChild, right: UiChild) -> Self {
+        Self {
+            left: Some(Box::new(left)),
+            right: Some(Box::new(right)),
+            ..Self::with_kind(UIChildKind::SPLIT)
+        }
+    }
+
+    /// Lay out `children` in a grid.
+    pub fn grid(children: Vec<UiChild>) -> Self {
+        Self {
+            children,
+            ..Self::with_kind(UIChildKind::GRID)
+        }
+    }
+
+    /// Display the state reached through `accessor`, e.g. `"stage.x"`.
+    pub fn state(accessor: &str) -> Self {
+        Self {
+            state: Some(accessor.to_string()),
+            ..Self::with_kind(UIChildKind::STATE)
+        }
+    }
+
+    /// Show the reservations of the dashboard's panels.
+    pub fn reservation() -> Self {
+        Self::with_kind(UIChildKind::RESERVATION)
+    }
+
+    /// Specify whether the node starts hidden.
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.hidden = Some(hidden);
+        self
+    }
+
+    pub fn build(self) -> UIChildInput {
+        UIChildInput {
+            state: self.state,
+            kind: self.kind,
+            hidden: self.hidden,
+            children: Box::new(
+                Some(self.children)
+                    .filter(|children| !children.is_empty())
+                    .map(|children| children.into_iter().map(UiChild::build).collect()),
+            ),
+            left: Box::new(self.left.map(|left| left.build())),
+            right: Box::new(self.right.map(|right| right.build())),
+        }
+    }
+}
+
+/// A panel of a dashboard: either a view on published state or a form
+/// assigning to a template.
+pub struct Panel {
+    input: create_panel::CreatePanelInput,
+}
+
+impl Panel {
+    fn with_kind(name: &str, kind: PanelKind) -> Self {
+        Self {
+            input: create_panel::CreatePanelInput {
+                name: name.to_string(),
+                kind,
+                state: None,
+                state_key: None,
+                reservation: None,
+                instance_id: None,
+                state_accessors: None,
+                interface: None,
+                args: None,
+                submit_on_change: Some(false),
+                submit_on_load: Some(false),
+            },
+        }
+    }
+
+    /// Show the state with id `state`, see `StatePublisher::id`.
+    pub fn state(name: &str, state: &str) -> Self {
+        let mut panel = Self::with_kind(name, PanelKind::STATE);
+        panel.input.state = Some(state.to_string());
+        panel
+    }
+
+    /// Only show the value under `key` of the state.
+    pub fn state_key(mut self, key: &str) -> Self {
+        self.input.state_key = Some(key.to_string());
+        self
+    }
+
+    /// Add an accessor into the state, e.g. `"stage.x"`.
+    pub fn accessor(mut self, accessor: &str) -> Self {
+        self.input
+            .state_accessors
+            .get_or_insert_with(Vec::new)
+            .push(accessor.to_string());
+        self
+    }
+
+    /// A form assigning to the template registered for `interface` on the
+    /// agent of `client`.
+    pub fn assign(name: &str, client: &RekuestClient, interface: &str) -> Self {
+        let mut panel = Self::with_kind(name, PanelKind::ASSIGN);
+        panel.input.instance_id = Some(client.instance_id().to_string());
+        panel.input.interface = Some(interface.to_string());
+        panel
+    }
+
+    /// A form assigning through the reservation with id `reservation`.
+    pub fn reservation(name: &str, reservation: &str) -> Self {
+        let mut panel = Self::with_kind(name, PanelKind::ASSIGN);
+        panel.input.reservation = Some(reservation.to_string());
+        panel
+    }
+
+    /// Prefill the form with `args`.
+    pub fn args(mut self, args: ValueMap) -> Self {
+        self.input.args = Some(args);
+        self
+    }
+
+    /// Assign whenever an argument changes.
+    pub fn submit_on_change(mut self, submit: bool) -> Self {
+        self.input.submit_on_change = Some(submit);
+        self
+    }
+
+    /// Assign as soon as the panel is shown.
+    pub fn submit_on_load(mut self, submit: bool) -> Self {
+        self.input.submit_on_load = Some(submit);
+        self
+    }
+}
+
+/// Builder for a dashboard made of panels and an optional UI tree.
+pub struct Dashboard {
+    name: String,
+    panels: Vec<Panel>,
+    tree: Option<UiChild>,
+}
+
+impl Dashboard {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            panels: Vec::new(),
+            tree: None,
+        }
+    }
+
+    pub fn panel(mut self, panel: Panel) -> Self {
+        self.panels.push(panel);
+        self
+    }
+
+    /// Arrange the dashboard with `root`.
+    pub fn tree(mut self, root: UiChild) -> Self {
+        self.tree = Some(root);
+        self
+    }
+
+    /// Return the id of the dashboard with this name that `agent` created
+    /// earlier, or create it.
+    ///
+    /// Meant to be called on every agent startup. A dashboard counts as the
+    /// agent's if all its panels show the agent's state or assign through
+    /// its reservations. rekuest offers no way to update a dashboard, so an
+    /// existing one is returned as is, even if the panels or tree changed;
+    /// rename the dashboard to have it created anew.
+    pub async fn find_or_create(
+        self,
+        client: &RekuestClient,
+        agent: &EnsureAgentEnsureAgent,
+    ) -> anyhow::Result<String> {
+        let request = Dashboards::build_query(dashboards::Variables {});
+        let response: Response<dashboards::ResponseData> =
+            client.request(&request).send().await?.json().await?;
+
+        let existing = match response.data {
+            Some(data) => data.dashboards,
+            None => anyhow::bail!("dashboards query failed: {:?}", response.errors),
+        };
+        if let Some(dashboard) = existing.into_iter().find(|dashboard| {
+            dashboard.name.as_deref() == Some(self.name.as_str()) && belongs_to(dashboard, agent)
+        }) {
+            return Ok(dashboard.id);
+        }
+
+        self.create(client).await
+    }
+
+    /// Create the panels and the dashboard, returning the dashboard id.
+    pub async fn create(self, client: &RekuestClient) -> anyhow::Result<String> {
+        let mut panels = Vec::new();
+        for panel in self.panels {
+            let request = CreatePanel::build_query(create_panel::Variables { input: panel.input });
+            let response: Response<create_panel::ResponseData> =
+                client.request(&request).send().await?.json().await?;
+
+            match response.data {
+                Some(data) => panels.push(data.create_panel.id),
+                None => anyhow::bail!("createPanel failed: {:?}", response.errors),
+            }
+        }
+
+        let request = CreateDashboard::build_query(create_dashboard::Variables {
+            input: create_dashboard::CreateDashboardInput {
+                name: Some(self.name),
+                panels: Some(panels),
+                tree: self.tree.map(|root| UITreeInput {
+                    child: Box::new(root.build()),
+                }),
+            },
+        });
+        let response: Response<create_dashboard::ResponseData> =
+            client.request(&request).send().await?.json().await?;
+
+        match response.data {
+            Some(data) => Ok(data.create_dashboard.id),
+            None => anyhow::bail!("createDashboard failed: {:?}", response.errors),
+        }
+    }
+}
+
+/// Whether every panel of `dashboard` (and there is at least one) shows the
+/// state of `agent` or assigns through a reservation it made.
+fn belongs_to(
+    dashboard: &dashboards::DashboardsDashboards,
+    agent: &EnsureAgentEnsureAgent,
+) -> bool {
+    let is_agent = |registry: &str, instance_id: &str| {
+        registry == agent.registry.id && instance_id == agent.instance_id
+    };
+    let panels = dashboard.panels.as_deref().unwrap_or_default();
+
+    !panels.is_empty()
+        && panels
+            .iter()
+            .all(|panel| match (&panel.state, &panel.reservation) {
+                (Some(state), _) => is_agent(&state.agent.registry.id, &state.agent.instance_id),
+                (None, Some(reservation)) => is_agent(
+                    &reservation.waiter.registry.id,
+                    &reservation.waiter.instance_id,
+                ),
+                (None, None) => false,
+            })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn agent() -> EnsureAgentEnsureAgent {
+        serde_json::from_value(json!({
+            "id": "1",
+            "instanceId": "default",
+            "extensions": ["default"],
+            "name": "Agent",
+            "registry": { "id": "7" },
+        }))
+        .unwrap()
+    }
+
+    fn dashboard(panels: Value) -> dashboards::DashboardsDashboards {
+        serde_json::from_value(json!({ "id": "1", "name": "Stage", "panels": panels })).unwrap()
+    }
+
+    fn owner(registry: &str, instance_id: &str) -> Value {
+        json!({ "instanceId": instance_id, "registry": { "id": registry } })
+    }
+
+    #[test]
+    fn dashboards_of_the_agent() {
+        let state = |registry, instance_id| json!({ "state": { "agent": owner(registry, instance_id) }, "reservation": null });
+        let reservation = |registry, instance_id| json!({ "state": null, "reservation": { "waiter": owner(registry, instance_id) } });
+
+        for (panels, expected) in [
+            (json!([state("7", "default")]), true),
+            (
+                json!([state("7", "default"), reservation("7", "default")]),
+                true,
+            ),
+            // Another app or user
+            (json!([state("8", "default")]), false),
+            // Another agent of the same app
+            (json!([reservation("7", "stage")]), false),
+            (
+                json!([state("7", "default"), reservation("8", "default")]),
+                false,
+            ),
+            (json!([{ "state": null, "reservation": null }]), false),
+            (json!([]), false),
+            (json!(null), false),
+        ] {
+            assert_eq!(
+                belongs_to(&dashboard(panels.clone()), &agent()),
+                expected,
+                "{}",
+                panels
+            );
+        }
+    }
+}
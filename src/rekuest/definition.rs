@@ -1,4 +1,97 @@
-use super::api::create_template::{DefinitionInput, NodeKind, PortGroupInput, PortInput};
+use std::collections::HashSet;
+use std::fmt;
+
+use super::api::create_template::{
+    ChildPortInput, DefinitionInput, NodeKind, PortGroupInput, PortInput, PortKind,
+};
+
+/// A rule violated by a definition, found by `Definition::try_build`, or by
+/// the args of an assignation, found by `check_args`.
+/// `port` is the path of the offending port, e.g. `args.images.image`.
+#[derive(Debug, Clone)]
+pub enum ValidationError {
+    DuplicateKey {
+        port: String,
+    },
+    MissingIdentifier {
+        port: String,
+    },
+    MissingChildren {
+        port: String,
+    },
+    InvalidDefault {
+        port: String,
+        kind: String,
+        default: serde_json::Value,
+    },
+    UnknownPortGroup {
+        port: String,
+        group: String,
+    },
+    EmptyPortGroup {
+        group: String,
+    },
+    MissingArg {
+        port: String,
+    },
+    InvalidArg {
+        port: String,
+        kind: String,
+        value: serde_json::Value,
+    },
+    UnknownArg {
+        port: String,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::DuplicateKey { port } => write!(f, "{}: duplicate port key", port),
+            ValidationError::MissingIdentifier { port } => {
+                write!(f, "{}: port needs an identifier", port)
+            }
+            ValidationError::MissingChildren { port } => {
+                write!(f, "{}: port needs child ports", port)
+            }
+            ValidationError::InvalidDefault {
+                port,
+                kind,
+                default,
+            } => write!(f, "{}: default {} is not a valid {}", port, default, kind),
+            ValidationError::UnknownPortGroup { port, group } => {
+                write!(f, "{}: port group {} is not declared", port, group)
+            }
+            ValidationError::EmptyPortGroup { group } => {
+                write!(f, "port group {} contains no ports", group)
+            }
+            ValidationError::MissingArg { port } => write!(f, "{}: missing value", port),
+            ValidationError::InvalidArg { port, kind, value } => {
+                write!(f, "{}: {} is not a valid {}", port, value, kind)
+            }
+            ValidationError::UnknownArg { port } => write!(f, "{}: no such port", port),
+        }
+    }
+}
+
+/// Returned by `Definition::try_build` if the definition breaks any rules.
+#[derive(Debug, Clone)]
+pub struct InvalidDefinition {
+    pub name: String,
+    pub errors: Vec<ValidationError>,
+}
+
+impl fmt::Display for InvalidDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid definition {}:", self.name)?;
+        for error in &self.errors {
+            write!(f, "\n  {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InvalidDefinition {}
 
 pub struct Definition {
     description: Option<String>,
@@ -21,7 +114,7 @@ impl Definition {
             description: None,
             name: name.to_string(),
             args: None,
-            kind: kind,
+            kind,
             port_groups: None,
             stateful: None,
             is_dev: None,
@@ -91,15 +184,547 @@ impl Definition {
         DefinitionInput {
             description: self.description,
             name: self.name,
-            args: self.args.unwrap_or_else(|| vec![]),
+            args: self.args.unwrap_or_default(),
             kind: self.kind,
-            port_groups: self.port_groups.unwrap_or_else(|| vec![]),
+            port_groups: self.port_groups.unwrap_or_default(),
             stateful: self.stateful.unwrap_or(false),
             is_dev: self.is_dev.unwrap_or(false),
-            is_test_for: self.is_test_for.unwrap_or_else(|| vec![]),
-            interfaces: self.interfaces.unwrap_or_else(|| vec![]),
-            returns: self.returns.unwrap_or_else(|| vec![]),
-            collections: self.collections.unwrap_or_else(|| vec![]),
+            is_test_for: self.is_test_for.unwrap_or_default(),
+            interfaces: self.interfaces.unwrap_or_default(),
+            returns: self.returns.unwrap_or_default(),
+            collections: self.collections.unwrap_or_default(),
+        }
+    }
+
+    /// Build the `DefinitionInput` and validate it locally, returning every
+    /// problem the server would otherwise reject `createTemplate` for.
+    pub fn try_build(self) -> Result<DefinitionInput, InvalidDefinition> {
+        let definition = self.build();
+        check_definition(&definition)?;
+        Ok(definition)
+    }
+}
+
+/// Validate a built `definition` locally, as `Definition::try_build` does.
+/// `FunctionRegistry::register` runs this for every template.
+pub fn check_definition(definition: &DefinitionInput) -> Result<(), InvalidDefinition> {
+    let errors = validate(definition);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(InvalidDefinition {
+            name: definition.name.clone(),
+            errors,
+        })
+    }
+}
+
+/// Check `args` against the arg ports of `definition`: every port without
+/// a default needs a value unless it is nullable, values must match the
+/// port's kind and there may be no values for unknown ports.
+pub fn check_args(
+    definition: &DefinitionInput,
+    args: &serde_json::Map<String, serde_json::Value>,
+) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+
+    for port in &definition.args {
+        let path = format!("args.{}", port.key);
+        match args.get(&port.key) {
+            None | Some(serde_json::Value::Null) if !port.nullable && port.default.is_none() => {
+                errors.push(ValidationError::MissingArg { port: path });
+            }
+            Some(value) if !default_matches(&port.kind, port.nullable, value) => {
+                errors.push(ValidationError::InvalidArg {
+                    port: path,
+                    kind: format!("{:?}", port.kind),
+                    value: value.clone(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    for key in args.keys() {
+        if !definition.args.iter().any(|port| &port.key == key) {
+            errors.push(ValidationError::UnknownArg {
+                port: format!("args.{}", key),
+            });
+        }
+    }
+
+    errors
+}
+
+/// The fields of `PortInput` and `ChildPortInput` the rules look at.
+struct PortView<'a> {
+    key: &'a str,
+    kind: &'a PortKind,
+    identifier: Option<&'a String>,
+    nullable: bool,
+    default: Option<&'a serde_json::Value>,
+    children: Option<&'a Vec<ChildPortInput>>,
+}
+
+impl<'a> From<&'a PortInput> for PortView<'a> {
+    fn from(port: &'a PortInput) -> Self {
+        PortView {
+            key: &port.key,
+            kind: &port.kind,
+            identifier: port.identifier.as_ref(),
+            nullable: port.nullable,
+            default: port.default.as_ref(),
+            children: port.children.as_ref(),
+        }
+    }
+}
+
+impl<'a> From<&'a ChildPortInput> for PortView<'a> {
+    fn from(port: &'a ChildPortInput) -> Self {
+        PortView {
+            key: &port.key,
+            kind: &port.kind,
+            identifier: port.identifier.as_ref(),
+            nullable: port.nullable,
+            default: port.default.as_ref(),
+            children: port.children.as_ref(),
+        }
+    }
+}
+
+fn validate(definition: &DefinitionInput) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+
+    for (section, ports) in [("args", &definition.args), ("returns", &definition.returns)] {
+        check_ports(section, ports.iter().map(PortView::from), &mut errors);
+    }
+
+    let declared: HashSet<&str> = definition
+        .port_groups
+        .iter()
+        .map(|group| group.key.as_str())
+        .collect();
+    let mut used = HashSet::new();
+    let sections = [("args", &definition.args), ("returns", &definition.returns)];
+    for (section, port) in sections
+        .iter()
+        .flat_map(|(section, ports)| ports.iter().map(move |port| (section, port)))
+    {
+        for group in port.groups.iter().flatten() {
+            used.insert(group.as_str());
+            if !declared.contains(group.as_str()) {
+                errors.push(ValidationError::UnknownPortGroup {
+                    port: format!("{}.{}", section, port.key),
+                    group: group.clone(),
+                });
+            }
+        }
+    }
+    for group in &definition.port_groups {
+        if !used.contains(group.key.as_str()) {
+            errors.push(ValidationError::EmptyPortGroup {
+                group: group.key.clone(),
+            });
+        }
+    }
+
+    errors
+}
+
+fn check_ports<'a>(
+    path: &str,
+    ports: impl Iterator<Item = PortView<'a>>,
+    errors: &mut Vec<ValidationError>,
+) {
+    let mut keys = HashSet::new();
+    for port in ports {
+        let port_path = format!("{}.{}", path, port.key);
+        if !keys.insert(port.key) {
+            errors.push(ValidationError::DuplicateKey {
+                port: port_path.clone(),
+            });
+        }
+        check_port(&port_path, &port, errors);
+    }
+}
+
+fn check_port(path: &str, port: &PortView, errors: &mut Vec<ValidationError>) {
+    let children = port.children.map(|c| c.len()).unwrap_or(0);
+
+    if matches!(port.kind, PortKind::STRUCTURE | PortKind::MODEL) && port.identifier.is_none() {
+        errors.push(ValidationError::MissingIdentifier {
+            port: path.to_string(),
+        });
+    }
+    if matches!(
+        port.kind,
+        PortKind::LIST | PortKind::DICT | PortKind::UNION | PortKind::MODEL
+    ) && children == 0
+    {
+        errors.push(ValidationError::MissingChildren {
+            port: path.to_string(),
+        });
+    }
+
+    if let Some(default) = port.default {
+        if !default_matches(port.kind, port.nullable, default) {
+            errors.push(ValidationError::InvalidDefault {
+                port: path.to_string(),
+                kind: format!("{:?}", port.kind),
+                default: default.clone(),
+            });
+        }
+    }
+
+    if let Some(children) = port.children {
+        check_ports(path, children.iter().map(PortView::from), errors);
+    }
+}
+
+fn default_matches(kind: &PortKind, nullable: bool, default: &serde_json::Value) -> bool {
+    use serde_json::Value;
+
+    match (kind, default) {
+        (_, Value::Null) => nullable,
+        (PortKind::INT, Value::Number(n)) => n.is_i64() || n.is_u64(),
+        (PortKind::FLOAT, Value::Number(_)) => true,
+        (PortKind::BOOL, Value::Bool(_)) => true,
+        (PortKind::STRING, Value::String(_)) => true,
+        (PortKind::DATE, Value::String(s)) => is_iso_date(s),
+        (PortKind::STRUCTURE, Value::String(_) | Value::Number(_)) => true,
+        (PortKind::LIST, Value::Array(_)) => true,
+        (PortKind::DICT | PortKind::MODEL, Value::Object(_)) => true,
+        (PortKind::UNION, _) => true,
+        _ => false,
+    }
+}
+
+/// Checks for an ISO 8601 date (`YYYY-MM-DD`), optionally followed by a time.
+fn is_iso_date(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    bytes.len() >= 10
+        && bytes[..10].iter().enumerate().all(|(i, b)| match i {
+            4 | 7 => *b == b'-',
+            _ => b.is_ascii_digit(),
+        })
+        && (bytes.len() == 10 || bytes[10] == b'T' || bytes[10] == b' ')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rekuest::ports::Port;
+    use serde_json::{json, Value};
+
+    fn definition(args: Vec<PortInput>, returns: Vec<PortInput>) -> Definition {
+        Definition::new("Test", NodeKind::FUNCTION)
+            .args(args)
+            .returns(returns)
+    }
+
+    fn errors(definition: Definition) -> Vec<String> {
+        match definition.try_build() {
+            Ok(_) => vec![],
+            Err(invalid) => invalid.errors.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    fn with_default(mut port: PortInput, default: Value) -> PortInput {
+        port.default = Some(default);
+        port
+    }
+
+    fn group(key: &str) -> PortGroupInput {
+        PortGroupInput {
+            key: key.to_string(),
+            hidden: false,
+        }
+    }
+
+    #[test]
+    fn definition_rules() {
+        let no_identifier = |mut port: PortInput| {
+            port.identifier = None;
+            port
+        };
+        let no_children = |mut port: PortInput| {
+            port.children = Some(vec![]);
+            port
+        };
+        let nested_default = |mut port: PortInput, default: Value| {
+            port.children.as_mut().unwrap()[0].default = Some(default);
+            port
+        };
+
+        let cases: Vec<(&str, Definition, Vec<&str>)> = vec![
+            (
+                "valid ports",
+                definition(
+                    vec![
+                        Port::new_int("count").default(3).build(),
+                        Port::new_float("scale").default(0.5).build(),
+                        Port::new_date("day").default("2024-01-02").build(),
+                        Port::new_list("sizes", Port::new_int("item")).build(),
+                        Port::new_dict("names", Port::new_string("value")).build(),
+                        Port::new_structure("image", "@mikro/image").build(),
+                        Port::new_union("either")
+                            .variant(Port::new_int("int"))
+                            .variant(Port::new_string("string"))
+                            .build(),
+                    ],
+                    vec![Port::new_int("count").build()],
+                ),
+                vec![],
+            ),
+            (
+                "duplicate key",
+                definition(
+                    vec![Port::new_int("a").build(), Port::new_string("a").build()],
+                    vec![],
+                ),
+                vec!["args.a: duplicate port key"],
+            ),
+            (
+                "duplicate key in returns",
+                definition(
+                    vec![],
+                    vec![Port::new_int("a").build(), Port::new_int("a").build()],
+                ),
+                vec!["returns.a: duplicate port key"],
+            ),
+            (
+                "structure without identifier",
+                definition(
+                    vec![no_identifier(
+                        Port::new_structure("image", "@mikro/image").build(),
+                    )],
+                    vec![],
+                ),
+                vec!["args.image: port needs an identifier"],
+            ),
+            (
+                "model without identifier",
+                definition(
+                    vec![no_identifier(
+                        Port::new_model("config", "@app/config")
+                            .field(Port::new_int("a"))
+                            .build(),
+                    )],
+                    vec![],
+                ),
+                vec!["args.config: port needs an identifier"],
+            ),
+            (
+                "model without fields",
+                definition(
+                    vec![Port::new_model("config", "@app/config").build()],
+                    vec![],
+                ),
+                vec!["args.config: port needs child ports"],
+            ),
+            (
+                "model without identifier or fields",
+                definition(
+                    vec![no_identifier(
+                        Port::new_model("config", "@app/config").build(),
+                    )],
+                    vec![],
+                ),
+                vec![
+                    "args.config: port needs an identifier",
+                    "args.config: port needs child ports",
+                ],
+            ),
+            (
+                "list without child",
+                definition(
+                    vec![no_children(
+                        Port::new_list("sizes", Port::new_int("item")).build(),
+                    )],
+                    vec![],
+                ),
+                vec!["args.sizes: port needs child ports"],
+            ),
+            (
+                "union without variants",
+                definition(vec![Port::new_union("either").build()], vec![]),
+                vec!["args.either: port needs child ports"],
+            ),
+            (
+                "defaults of the wrong kind",
+                definition(
+                    vec![
+                        with_default(Port::new_int("int").build(), json!("3")),
+                        with_default(Port::new_int("fraction").build(), json!(1.5)),
+                        with_default(Port::new_bool("flag").build(), json!(1)),
+                        with_default(Port::new_date("day").build(), json!("2024-1-2")),
+                        with_default(Port::new_string("name").build(), Value::Null),
+                    ],
+                    vec![],
+                ),
+                vec![
+                    "args.int: default \"3\" is not a valid INT",
+                    "args.fraction: default 1.5 is not a valid INT",
+                    "args.flag: default 1 is not a valid BOOL",
+                    "args.day: default \"2024-1-2\" is not a valid DATE",
+                    "args.name: default null is not a valid STRING",
+                ],
+            ),
+            (
+                "null default of a nullable port",
+                definition(
+                    vec![with_default(
+                        Port::new_string("name").nullable(true).build(),
+                        Value::Null,
+                    )],
+                    vec![],
+                ),
+                vec![],
+            ),
+            (
+                "invalid default of a list child",
+                definition(
+                    vec![nested_default(
+                        Port::new_list("sizes", Port::new_int("item")).build(),
+                        json!("big"),
+                    )],
+                    vec![],
+                ),
+                vec!["args.sizes.item: default \"big\" is not a valid INT"],
+            ),
+            (
+                "nested list without child",
+                definition(
+                    vec![{
+                        let mut port =
+                            Port::new_dict("rows", Port::new_list("value", Port::new_int("item")))
+                                .build();
+                        port.children.as_mut().unwrap()[0].children = Some(vec![]);
+                        port
+                    }],
+                    vec![],
+                ),
+                vec!["args.rows.value: port needs child ports"],
+            ),
+            (
+                "duplicate model fields",
+                definition(
+                    vec![Port::new_model("config", "@app/config")
+                        .field(Port::new_int("a"))
+                        .field(Port::new_string("a"))
+                        .build()],
+                    vec![],
+                ),
+                vec!["args.config.a: duplicate port key"],
+            ),
+            (
+                "undeclared port group",
+                definition(
+                    vec![Port::new_int("a").groups(vec!["advanced"]).build()],
+                    vec![],
+                ),
+                vec!["args.a: port group advanced is not declared"],
+            ),
+            (
+                "empty port group",
+                definition(vec![Port::new_int("a").build()], vec![])
+                    .port_groups(vec![group("advanced")]),
+                vec!["port group advanced contains no ports"],
+            ),
+            (
+                "used port group",
+                definition(
+                    vec![Port::new_int("a").groups(vec!["advanced"]).build()],
+                    vec![],
+                )
+                .port_groups(vec![group("advanced")]),
+                vec![],
+            ),
+        ];
+
+        for (name, definition, expected) in cases {
+            assert_eq!(errors(definition), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn arg_rules() {
+        let definition = definition(
+            vec![
+                Port::new_int("count").build(),
+                Port::new_string("label").nullable(true).build(),
+                Port::new_float("scale").default(1.0).build(),
+                Port::new_list("sizes", Port::new_int("item")).build(),
+            ],
+            vec![],
+        )
+        .build();
+        let check = |args: Value| -> Vec<String> {
+            check_args(&definition, args.as_object().unwrap())
+                .iter()
+                .map(|e| e.to_string())
+                .collect()
+        };
+
+        let cases: Vec<(&str, Value, Vec<&str>)> = vec![
+            (
+                "required args only",
+                json!({ "count": 1, "sizes": [] }),
+                vec![],
+            ),
+            (
+                "all args",
+                json!({ "count": 1, "label": "x", "scale": 2, "sizes": [1] }),
+                vec![],
+            ),
+            (
+                "missing arg",
+                json!({ "sizes": [] }),
+                vec!["args.count: missing value"],
+            ),
+            (
+                "null for a required arg",
+                json!({ "count": null, "sizes": [] }),
+                vec!["args.count: missing value"],
+            ),
+            (
+                "null for a nullable arg",
+                json!({ "count": 1, "label": null, "sizes": [] }),
+                vec![],
+            ),
+            (
+                "arg of the wrong kind",
+                json!({ "count": "one", "sizes": {} }),
+                vec![
+                    "args.count: \"one\" is not a valid INT",
+                    "args.sizes: {} is not a valid LIST",
+                ],
+            ),
+            (
+                "unknown arg",
+                json!({ "count": 1, "sizes": [], "colour": "red" }),
+                vec!["args.colour: no such port"],
+            ),
+        ];
+
+        for (name, args, expected) in cases {
+            assert_eq!(check(args), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn iso_dates() {
+        for (value, valid) in [
+            ("2024-01-02", true),
+            ("2024-01-02T10:00:00", true),
+            ("2024-01-02 10:00", true),
+            ("2024-1-2", false),
+            ("2024/01/02", false),
+            ("2024-01-02X", false),
+            ("02-01-2024", false),
+            ("", false),
+        ] {
+            assert_eq!(is_iso_date(value), valid, "{}", value);
         }
     }
 }
@@ -0,0 +1,101 @@
+use super::api::create_template::{
+    EffectDependencyInput, EffectInput, EffectKind, LogicalCondition,
+};
+
+/// Builder for an `EffectInput`.
+///
+/// An effect is attached to the port it affects and becomes active while all
+/// of its dependencies hold, e.g. "explain port X when Y equals Z":
+///
+/// ```ignore
+/// Port::new_int("x").effect(Effect::message("Only used in fast mode").when("y").is("z"))
+/// ```
+pub struct Effect {
+    kind: EffectKind,
+    label: String,
+    description: Option<String>,
+    dependencies: Vec<EffectDependencyInput>,
+}
+
+/// A dependency of an `Effect` waiting for its condition, see `Effect::when`.
+pub struct EffectCondition {
+    effect: Effect,
+    key: String,
+}
+
+impl Effect {
+    fn new(kind: EffectKind, label: &str) -> Self {
+        Self {
+            kind,
+            label: label.to_string(),
+            description: None,
+            dependencies: Vec::new(),
+        }
+    }
+
+    /// Show `message` next to the port.
+    pub fn message(message: &str) -> Self {
+        Self::new(EffectKind::MESSAGE, message)
+    }
+
+    /// A CUSTOM effect labelled `label`. Clients resolve custom effects
+    /// through a hook and ward, which `EffectInput` cannot set.
+    pub fn custom(label: &str) -> Self {
+        Self::new(EffectKind::CUSTOM, label)
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    /// Add a condition on the value of port `key`.
+    pub fn when(self, key: &str) -> EffectCondition {
+        EffectCondition {
+            effect: self,
+            key: key.to_string(),
+        }
+    }
+
+    pub fn build(self) -> EffectInput {
+        EffectInput {
+            label: self.label,
+            description: self.description,
+            dependencies: self.dependencies,
+            kind: self.kind,
+        }
+    }
+}
+
+impl EffectCondition {
+    fn condition(mut self, condition: LogicalCondition, value: serde_json::Value) -> Effect {
+        self.effect.dependencies.push(EffectDependencyInput {
+            key: self.key,
+            condition,
+            value,
+        });
+        self.effect
+    }
+
+    /// The port must equal `value`.
+    pub fn is(self, value: impl Into<serde_json::Value>) -> Effect {
+        self.condition(LogicalCondition::IS, value.into())
+    }
+
+    /// The port must not equal `value`.
+    pub fn is_not(self, value: impl Into<serde_json::Value>) -> Effect {
+        self.condition(LogicalCondition::IS_NOT, value.into())
+    }
+
+    /// The port must equal one of `values`.
+    pub fn is_in<V: Into<serde_json::Value>>(self, values: impl IntoIterator<Item = V>) -> Effect {
+        let values = values.into_iter().map(Into::into).collect();
+        self.condition(LogicalCondition::IN, serde_json::Value::Array(values))
+    }
+}
+
+impl From<Effect> for EffectInput {
+    fn from(effect: Effect) -> Self {
+        effect.build()
+    }
+}
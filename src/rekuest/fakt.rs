@@ -8,5 +8,23 @@ pub struct AgentFakt {
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct RekuestFakt {
     pub endpoint_url: String,
+    /// Websocket endpoint for subscriptions. Derived from `endpoint_url`
+    /// when the fakts server does not provide one.
+    #[serde(default)]
+    pub ws_endpoint_url: Option<String>,
+    #[serde(default)]
+    pub hardware: HardwareFakt,
     pub agent: AgentFakt,
 }
+
+/// Overrides for the hardware facts the agent reports. Unset fields are
+/// collected from the machine.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct HardwareFakt {
+    pub cpu_count: Option<i64>,
+    /// In MHz.
+    pub cpu_frequency: Option<f64>,
+    pub cpu_vendor_name: Option<String>,
+    /// Seconds between two reports, defaults to ten minutes.
+    pub refresh_interval: Option<u64>,
+}
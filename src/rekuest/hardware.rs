@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+use graphql_client::GraphQLQuery;
+use graphql_client::Response;
+use tokio::task::JoinHandle;
+
+use super::api::create_hardware_record;
+use super::api::CreateHardwareRecord;
+use super::client::RekuestClient;
+use super::fakt::HardwareFakt;
+
+const DEFAULT_REFRESH_INTERVAL: u64 = 600;
+
+/// Hardware facts about the machine the agent runs on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HardwareInfo {
+    pub cpu_count: i64,
+    /// In MHz, the highest frequency any core reports. `None` if no core
+    /// reports one, e.g. on most ARM machines.
+    pub cpu_frequency: Option<f64>,
+    pub cpu_vendor_name: String,
+}
+
+impl HardwareInfo {
+    /// Collect the facts from `/proc`, falling back to what the standard
+    /// library knows on other systems.
+    pub fn collect() -> Self {
+        Self::from_cpuinfo(&std::fs::read_to_string("/proc/cpuinfo").unwrap_or_default())
+    }
+
+    /// The facts in the contents of `/proc/cpuinfo`.
+    fn from_cpuinfo(cpuinfo: &str) -> Self {
+        let processors = cpuinfo
+            .lines()
+            .filter(|line| proc_key(line) == Some("processor"))
+            .count() as i64;
+        let cpu_count = match processors {
+            0 => std::thread::available_parallelism()
+                .map(|n| n.get() as i64)
+                .unwrap_or(1),
+            n => n,
+        };
+
+        let cpu_frequency = cpuinfo
+            .lines()
+            .filter(|line| proc_key(line) == Some("cpu MHz"))
+            .filter_map(|line| proc_value(line)?.parse::<f64>().ok())
+            .reduce(f64::max);
+
+        let cpu_vendor_name = cpuinfo
+            .lines()
+            .find(|line| proc_key(line) == Some("vendor_id"))
+            .and_then(proc_value)
+            .unwrap_or("unknown")
+            .to_string();
+
+        Self {
+            cpu_count,
+            cpu_frequency,
+            cpu_vendor_name,
+        }
+    }
+
+    /// Replace collected facts with the ones set in `overrides`.
+    pub fn with_overrides(mut self, overrides: &HardwareFakt) -> Self {
+        if let Some(cpu_count) = overrides.cpu_count {
+            self.cpu_count = cpu_count;
+        }
+        if let Some(cpu_frequency) = overrides.cpu_frequency {
+            self.cpu_frequency = Some(cpu_frequency);
+        }
+        if let Some(cpu_vendor_name) = &overrides.cpu_vendor_name {
+            self.cpu_vendor_name = cpu_vendor_name.clone();
+        }
+        self
+    }
+}
+
+/// The key of a `key : value` line of `/proc/cpuinfo`.
+fn proc_key(line: &str) -> Option<&str> {
+    line.split_once(':').map(|(key, _)| key.trim())
+}
+
+fn proc_value(line: &str) -> Option<&str> {
+    line.split_once(':').map(|(_, value)| value.trim())
+}
+
+/// Submit `info` as the hardware record of the client's instance.
+pub async fn create_hardware_record(
+    client: &RekuestClient,
+    info: &HardwareInfo,
+) -> anyhow::Result<String> {
+    let request = CreateHardwareRecord::build_query(create_hardware_record::Variables {
+        input: create_hardware_record::CreateHardwareRecordInput {
+            cpu_count: Some(info.cpu_count),
+            cpu_frequency: info.cpu_frequency,
+            cpu_vendor_name: Some(info.cpu_vendor_name.clone()),
+            instance_id: Some(client.instance_id().to_string()),
+        },
+    });
+
+    let response: Response<create_hardware_record::ResponseData> =
+        client.request(&request).send().await?.json().await?;
+
+    match response.data {
+        Some(data) => Ok(data.create_hardware_record.id),
+        None => anyhow::bail!("createHardwareRecord failed: {:?}", response.errors),
+    }
+}
+
+/// Report the hardware now and then every `refresh_interval` seconds of
+/// `overrides`, until the returned task is aborted.
+pub fn report_hardware(client: RekuestClient, overrides: HardwareFakt) -> JoinHandle<()> {
+    let interval = overrides
+        .refresh_interval
+        .unwrap_or(DEFAULT_REFRESH_INTERVAL);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval.max(1)));
+        loop {
+            ticker.tick().await;
+            let info = HardwareInfo::collect().with_overrides(&overrides);
+            if let Err(e) = create_hardware_record(&client, &info).await {
+                println!("Failed to report hardware: {}", e);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const X86: &str = "processor\t: 0\nvendor_id\t: GenuineIntel\ncpu MHz\t\t: 2400.000\n\n\
+                       processor\t: 1\nvendor_id\t: GenuineIntel\ncpu MHz\t\t: 3100.5\n";
+    const ARM: &str = "processor\t: 0\nBogoMIPS\t: 48.00\n\nprocessor\t: 1\nBogoMIPS\t: 48.00\n";
+
+    #[test]
+    fn reads_cpuinfo() {
+        let info = HardwareInfo::from_cpuinfo(X86);
+        assert_eq!(info.cpu_count, 2);
+        assert_eq!(info.cpu_frequency, Some(3100.5));
+        assert_eq!(info.cpu_vendor_name, "GenuineIntel");
+    }
+
+    #[test]
+    fn unknown_frequency_is_none() {
+        let info = HardwareInfo::from_cpuinfo(ARM);
+        assert_eq!(info.cpu_count, 2);
+        assert_eq!(info.cpu_frequency, None);
+        assert_eq!(info.cpu_vendor_name, "unknown");
+    }
+
+    #[test]
+    fn overrides_win() {
+        let overrides = HardwareFakt {
+            cpu_count: Some(8),
+            cpu_frequency: Some(1000.0),
+            ..Default::default()
+        };
+        let info = HardwareInfo::from_cpuinfo(ARM).with_overrides(&overrides);
+        assert_eq!(info.cpu_count, 8);
+        assert_eq!(info.cpu_frequency, Some(1000.0));
+        assert_eq!(info.cpu_vendor_name, "unknown");
+    }
+}
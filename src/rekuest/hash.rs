@@ -0,0 +1,230 @@
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use super::api::create_template::DefinitionInput;
+
+/// The definition fields that make up a node's identity. Everything else
+/// (port groups, interfaces, kind, ...) can change without creating a new node.
+const HASHED_FIELDS: [&str; 7] = [
+    "name",
+    "description",
+    "args",
+    "returns",
+    "stateful",
+    "is_test_for",
+    "collections",
+];
+
+/// Keys whose values are user data and must not be renamed.
+const OPAQUE_FIELDS: [&str; 2] = ["default", "value"];
+
+/// Compute the `NodeHash` of a definition.
+///
+/// Mirrors the rekuest server: the hashed fields are dumped with snake_case
+/// keys, serialized like python's `json.dumps(..., sort_keys=True)` and
+/// hashed with SHA-256. Two definitions hash equal iff the server treats them
+/// as the same node.
+pub fn hash_definition(definition: &DefinitionInput) -> String {
+    hash_definition_value(&serde_json::to_value(definition).expect("definitions always serialize"))
+}
+
+/// `hash_definition` of a definition as sent over the wire.
+pub(crate) fn hash_definition_value(value: &Value) -> String {
+    let mut hashable = serde_json::Map::new();
+    if let Value::Object(fields) = value {
+        for (key, value) in fields {
+            let key = snake_case(key);
+            if HASHED_FIELDS.contains(&key.as_str()) {
+                hashable.insert(key, snake_case_keys(value.clone()));
+            }
+        }
+    }
+
+    let canonical = canonical_json(&Value::Object(hashable));
+
+    Sha256::digest(canonical.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn snake_case(key: &str) -> String {
+    let mut snake = String::with_capacity(key.len() + 4);
+    for c in key.chars() {
+        if c.is_ascii_uppercase() {
+            snake.push('_');
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+fn snake_case_keys(value: Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| {
+                    if OPAQUE_FIELDS.contains(&key.as_str()) {
+                        (key, value)
+                    } else {
+                        (snake_case(&key), snake_case_keys(value))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(snake_case_keys).collect()),
+        other => other,
+    }
+}
+
+/// `value` serialized with sorted keys, so equal values always give the
+/// same string.
+pub(crate) fn canonical_json(value: &Value) -> String {
+    let mut canonical = String::new();
+    write_python_json(value, &mut canonical);
+    canonical
+}
+
+/// Serialize `value` exactly like python's `json.dumps(value, sort_keys=True)`.
+fn write_python_json(value: &Value, out: &mut String) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => match n.as_f64() {
+            Some(f) if n.is_f64() => out.push_str(&python_float(f)),
+            _ => out.push_str(&n.to_string()),
+        },
+        Value::String(s) => write_python_string(s, out),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_python_json(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(fields) => {
+            let mut keys: Vec<&String> = fields.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_python_string(key, out);
+                out.push_str(": ");
+                write_python_json(&fields[key], out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+/// Python's `repr(float)`: always has a decimal point or an exponent, and
+/// exponents carry a sign and at least two digits.
+fn python_float(f: f64) -> String {
+    let repr = format!("{:?}", f);
+    match repr.split_once('e') {
+        Some((mantissa, exponent)) => {
+            let (sign, digits) = match exponent.strip_prefix('-') {
+                Some(digits) => ('-', digits),
+                None => ('+', exponent),
+            };
+            format!("{}e{}{:0>2}", mantissa, sign, digits)
+        }
+        None => repr,
+    }
+}
+
+/// A JSON string literal with python's default `ensure_ascii=True` escaping.
+fn write_python_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{08}' => out.push_str("\\b"),
+            '\u{0c}' => out.push_str("\\f"),
+            // Printable ASCII only, python escapes DEL as well.
+            ' '..='~' => out.push(c),
+            c => {
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    out.push_str(&format!("\\u{:04x}", unit));
+                }
+            }
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rekuest::api::create_template::NodeKind;
+    use crate::rekuest::definition::Definition;
+    use crate::rekuest::ports::Port;
+    use serde_json::json;
+
+    // The expected values below were produced with python 3's
+    // `json.dumps(value, sort_keys=True)` and `hashlib.sha256`, which is how
+    // the rekuest server hashes definitions.
+
+    #[test]
+    fn canonical_json_matches_python() {
+        let value = json!({
+            "b": [1.0, 0.1, 1e-7, 1e16, 123456789012345.6, -0.0, 2.5e-5, 1e22, 3],
+            "a": { "z": "tab\t\"q\" \\ \u{7f} \u{1f}", "é": null, "A": true },
+        });
+        assert_eq!(
+            canonical_json(&value),
+            r#"{"a": {"A": true, "z": "tab\t\"q\" \\ \u007f \u001f", "\u00e9": null}, "b": [1.0, 0.1, 1e-07, 1e+16, 123456789012345.6, -0.0, 2.5e-05, 1e+22, 3]}"#
+        );
+    }
+
+    #[test]
+    fn hash_definition_matches_python() {
+        let definition = Definition::new("Threshold \"Größe\"", NodeKind::FUNCTION)
+            .description("Binarize an image.\nValues ≥ threshold become 1 😀")
+            .args(vec![
+                Port::new_float("threshold").default(0.5).build(),
+                Port::new_int("iterations")
+                    .default(3)
+                    .nullable(true)
+                    .build(),
+            ])
+            .returns(vec![Port::new_string("label").build()])
+            .collections(vec!["segmentation"])
+            .build();
+
+        assert_eq!(
+            hash_definition(&definition),
+            "561f967a607e2db8d41ada3b728fde89384e89ac3445af204748942134547612"
+        );
+    }
+
+    #[test]
+    fn hash_ignores_unhashed_fields() {
+        let definition = || {
+            Definition::new("Add", NodeKind::FUNCTION)
+                .args(vec![Port::new_int("a").build()])
+                .returns(vec![Port::new_int("sum").build()])
+        };
+        assert_eq!(
+            hash_definition(&definition().build()),
+            hash_definition(&definition().is_dev(true).interfaces(vec!["math"]).build()),
+        );
+        assert_ne!(
+            hash_definition(&definition().build()),
+            hash_definition(&definition().description("Adds").build()),
+        );
+    }
+}
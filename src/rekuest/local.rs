@@ -0,0 +1,139 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use super::api::create_template::{PortInput, PortKind};
+use super::context::Context;
+use super::definition::check_args;
+use super::registry::FunctionRegistry;
+use super::registry::ValueMap;
+
+/// A local invocation of a registered function, as the `run` command of
+/// `cli::AgentApp` builds it:
+///
+/// ```text
+/// run <interface> [--arg key=value]... [--args args.json] [--out returns.json]
+/// ```
+///
+/// `--arg` values are converted by the kind of the port they are for, so
+/// `--arg count=3` passes a number to an INT port. Ports without a simple
+/// kind take JSON, e.g. `--arg sizes=[1,2]`. `--arg` wins over `--args`.
+#[derive(Debug, Clone, Default)]
+pub struct LocalRun {
+    pub interface: String,
+    pub args: Vec<(String, String)>,
+    pub args_file: Option<PathBuf>,
+    /// Write the returns here instead of printing them.
+    pub out: Option<PathBuf>,
+}
+
+impl<S: Clone + Send + Sync + 'static> FunctionRegistry<S> {
+    /// Call the function registered for `run.interface` in-process, without
+    /// a server. Args are checked against the template's ports first, logs
+    /// and progress are printed, and the returns are printed as JSON or
+    /// written to `run.out`.
+    pub async fn run_local(&self, state: S, run: &LocalRun) -> Result<ValueMap, Box<dyn Error>> {
+        let template = self.template_for(&run.interface).ok_or_else(|| {
+            let mut interfaces: Vec<&str> = self.interfaces().collect();
+            interfaces.sort();
+            format!(
+                "No template registered for {}, available: {}",
+                run.interface,
+                interfaces.join(", ")
+            )
+        })?;
+        let definition = &template.definition;
+
+        let mut args = read_args(
+            &run.interface,
+            Some(&definition.args),
+            &run.args,
+            run.args_file.as_deref(),
+        )?;
+        for port in &definition.args {
+            if let Some(default) = &port.default {
+                args.entry(port.key.clone())
+                    .or_insert_with(|| default.clone());
+            }
+        }
+
+        let errors = check_args(definition, &args);
+        if !errors.is_empty() {
+            let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+            return Err(format!("Invalid args:\n{}", errors.join("\n")).into());
+        }
+
+        let function = self
+            .function_for(&run.interface)
+            .ok_or_else(|| format!("No function registered for {}", run.interface))?;
+        let returns = function(Context::local(state), args).await?;
+
+        let json = serde_json::to_string_pretty(&returns)?;
+        match &run.out {
+            Some(path) => std::fs::write(path, json)?,
+            None => println!("{}", json),
+        }
+        Ok(returns)
+    }
+}
+
+/// Collect the args of `node` from a JSON file and `key=value` pairs, the
+/// pairs winning. With `ports`, values are converted by the kind of their
+/// port and unknown keys are an error; without, values are read as JSON,
+/// falling back to plain strings.
+pub(crate) fn read_args(
+    node: &str,
+    ports: Option<&[PortInput]>,
+    raw_args: &[(String, String)],
+    file: Option<&Path>,
+) -> Result<ValueMap, Box<dyn Error>> {
+    let mut args = match file {
+        Some(path) => match serde_json::from_str(&std::fs::read_to_string(path)?)? {
+            Value::Object(args) => args,
+            other => return Err(format!("{} is not a JSON map: {}", path.display(), other).into()),
+        },
+        None => ValueMap::new(),
+    };
+
+    for (key, raw) in raw_args {
+        let value = match ports {
+            Some(ports) => {
+                let port = ports
+                    .iter()
+                    .find(|port| &port.key == key)
+                    .ok_or_else(|| format!("{} has no arg {}", node, key))?;
+                parse_arg(port, raw)?
+            }
+            None => serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.clone())),
+        };
+        args.insert(key.clone(), value);
+    }
+
+    Ok(args)
+}
+
+/// Split a `key=value` command line arg.
+pub(crate) fn split_arg(arg: &str) -> Result<(String, String), String> {
+    let (key, value) = arg
+        .split_once('=')
+        .ok_or(format!("--arg {} is not key=value", arg))?;
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Convert a `--arg` value for `port`.
+fn parse_arg(port: &PortInput, raw: &str) -> Result<Value, String> {
+    let invalid = |e: &dyn std::fmt::Display| format!("Invalid value for {}: {}", port.key, e);
+    match port.kind {
+        PortKind::INT => raw.parse::<i64>().map(Value::from).map_err(|e| invalid(&e)),
+        PortKind::FLOAT => raw.parse::<f64>().map(Value::from).map_err(|e| invalid(&e)),
+        PortKind::BOOL => raw
+            .parse::<bool>()
+            .map(Value::from)
+            .map_err(|e| invalid(&e)),
+        PortKind::STRING | PortKind::DATE | PortKind::STRUCTURE => {
+            Ok(Value::String(raw.to_string()))
+        }
+        _ => serde_json::from_str(raw).map_err(|e| invalid(&e)),
+    }
+}
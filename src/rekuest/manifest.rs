@@ -0,0 +1,241 @@
+use std::error::Error;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::client::RekuestClient;
+use super::hash::hash_definition;
+use super::registry::{
+    compare_hashes, matches_server, server_templates, FunctionRegistry, TemplateDrift,
+};
+
+/// How a manifest is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    Json,
+    Yaml,
+}
+
+impl ManifestFormat {
+    /// YAML for `.yaml` and `.yml` files, JSON otherwise.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml") | Some("yml") => ManifestFormat::Yaml,
+            _ => ManifestFormat::Json,
+        }
+    }
+}
+
+/// Every template an app registers, in a stable order, meant to be checked
+/// into git and compared in CI.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateManifest {
+    pub templates: Vec<ManifestTemplate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestTemplate {
+    pub interface: String,
+    /// The `NodeHash` of the definition, as the server computes it.
+    pub hash: String,
+    /// The full `TemplateInput`: definition, dependencies, params, ...
+    pub template: serde_json::Value,
+}
+
+impl TemplateManifest {
+    pub fn parse(manifest: &str, format: ManifestFormat) -> Result<Self, Box<dyn Error>> {
+        Ok(match format {
+            ManifestFormat::Json => serde_json::from_str(manifest)?,
+            ManifestFormat::Yaml => serde_yaml::from_str(manifest)?,
+        })
+    }
+
+    pub fn to_string(&self, format: ManifestFormat) -> Result<String, Box<dyn Error>> {
+        Ok(match format {
+            ManifestFormat::Json => serde_json::to_string_pretty(self)? + "\n",
+            ManifestFormat::Yaml => serde_yaml::to_string(self)?,
+        })
+    }
+
+    /// Read a manifest, in the format its extension suggests.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::parse(
+            &std::fs::read_to_string(path)?,
+            ManifestFormat::from_path(path),
+        )
+    }
+
+    /// Write the manifest, in the format its extension suggests.
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        std::fs::write(path, self.to_string(ManifestFormat::from_path(path))?)?;
+        Ok(())
+    }
+
+    /// How the templates of this manifest differ from those of `other`,
+    /// e.g. the registered templates from a checked in manifest. Templates
+    /// with the same definition are compared in full, so changed
+    /// dependencies, params or port groups are reported too.
+    pub fn compare(&self, other: &TemplateManifest) -> Vec<TemplateDrift> {
+        let mut drift = compare_hashes(&self.hashes(), &other.hashes());
+        for template in &self.templates {
+            let reconfigured = other.templates.iter().any(|other| {
+                other.interface == template.interface
+                    && other.hash == template.hash
+                    && other.template != template.template
+            });
+            if reconfigured {
+                drift.push(TemplateDrift::Reconfigured {
+                    interface: template.interface.clone(),
+                });
+            }
+        }
+        drift
+    }
+
+    /// How the templates of this manifest differ from the templates the
+    /// agent with id `agent` has on the server for `extension`: definitions
+    /// by hash, params and dependencies field by field.
+    pub async fn diff(
+        &self,
+        client: &RekuestClient,
+        agent: &str,
+        extension: &str,
+    ) -> Result<Vec<TemplateDrift>, Box<dyn Error>> {
+        let server = server_templates(client, agent, extension).await?;
+
+        let hashes: Vec<(String, String)> = server
+            .iter()
+            .map(|t| (t.interface.clone(), t.node.hash.clone()))
+            .collect();
+        let mut drift = compare_hashes(&self.hashes(), &hashes);
+        for template in &self.templates {
+            let Some(server) = server
+                .iter()
+                .find(|t| t.interface == template.interface && t.node.hash == template.hash)
+            else {
+                continue;
+            };
+            let dependencies = serde_json::to_value(&server.dependencies)?;
+            if !matches_server(&template.template, &server.params, &dependencies) {
+                drift.push(TemplateDrift::Reconfigured {
+                    interface: template.interface.clone(),
+                });
+            }
+        }
+        Ok(drift)
+    }
+
+    fn hashes(&self) -> Vec<(String, String)> {
+        self.templates
+            .iter()
+            .map(|t| (t.interface.clone(), t.hash.clone()))
+            .collect()
+    }
+}
+
+impl<S: Clone + Send + Sync + 'static> FunctionRegistry<S> {
+    /// The manifest of all registered templates.
+    pub fn manifest(&self) -> Result<TemplateManifest, Box<dyn Error>> {
+        let mut templates = Vec::new();
+        for template in self.templates() {
+            templates.push(ManifestTemplate {
+                interface: template.interface.clone(),
+                hash: hash_definition(&template.definition),
+                template: serde_json::to_value(template)?,
+            });
+        }
+        Ok(TemplateManifest { templates })
+    }
+
+    /// Serialize all registered templates as a manifest.
+    pub fn export_manifest(&self, format: ManifestFormat) -> Result<String, Box<dyn Error>> {
+        self.manifest()?.to_string(format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rekuest::api::create_template::NodeKind;
+    use crate::rekuest::definition::Definition;
+    use crate::rekuest::ports::Port;
+    use crate::rekuest::template::{Dependency, Template};
+
+    fn registry(template: Template) -> FunctionRegistry<()> {
+        let mut registry = FunctionRegistry::new();
+        registry
+            .register(template.build(), |_, _: serde_json::Value| async {
+                Ok::<_, String>(())
+            })
+            .unwrap();
+        registry
+    }
+
+    fn add(description: &str) -> Template {
+        let definition = Definition::new("Add", NodeKind::FUNCTION)
+            .description(description)
+            .args(vec![Port::new_int("a").default(1).build()])
+            .returns(vec![Port::new_int("sum").build()])
+            .build();
+        Template::new("add", definition)
+    }
+
+    #[test]
+    fn round_trips_through_json_and_yaml() {
+        let manifest = registry(add("Adds").params(serde_json::json!({ "gpu": true })))
+            .manifest()
+            .unwrap();
+        for format in [ManifestFormat::Json, ManifestFormat::Yaml] {
+            let text = manifest.to_string(format).unwrap();
+            assert_eq!(TemplateManifest::parse(&text, format).unwrap(), manifest);
+        }
+    }
+
+    #[test]
+    fn identical_manifests_do_not_drift() {
+        let manifest = registry(add("Adds")).manifest().unwrap();
+        assert!(manifest.compare(&manifest.clone()).is_empty());
+    }
+
+    #[test]
+    fn changed_definitions_drift() {
+        let local = registry(add("Adds")).manifest().unwrap();
+        let checked_in = registry(add("Adds two ints")).manifest().unwrap();
+        assert!(matches!(
+            local.compare(&checked_in).as_slice(),
+            [TemplateDrift::Changed { interface, .. }] if interface == "add"
+        ));
+    }
+
+    #[test]
+    fn changed_dependencies_drift() {
+        let local = registry(add("Adds").dependency(Dependency::new("log", "abc")))
+            .manifest()
+            .unwrap();
+        let checked_in = registry(add("Adds")).manifest().unwrap();
+        assert_eq!(
+            local.compare(&checked_in),
+            vec![TemplateDrift::Reconfigured {
+                interface: "add".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn added_and_removed_templates_drift() {
+        let local = registry(add("Adds")).manifest().unwrap();
+        let empty = TemplateManifest { templates: vec![] };
+        assert_eq!(
+            local.compare(&empty),
+            vec![TemplateDrift::Missing {
+                interface: "add".to_string()
+            }]
+        );
+        assert_eq!(
+            empty.compare(&local),
+            vec![TemplateDrift::Stale {
+                interface: "add".to_string()
+            }]
+        );
+    }
+}
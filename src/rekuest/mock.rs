@@ -0,0 +1,697 @@
+//! An in-process stand-in for rekuest, to test agents without Arkitekt.
+//!
+//! `MockRekuest::start` serves a small subset of the GraphQL API over HTTP
+//! (`ensureAgent`, `createTemplate`, `createForeignTemplate`,
+//! `setExtensionTemplates` with `runCleanup`, the agent's templates,
+//! `provision` and `assign` by template), the `assignationEvents`
+//! subscription and the agent websocket, so functions can call templates of their own agent. Tests
+//! then script `PROVIDE`/`ASSIGN` messages and assert on the
+//! `ASSIGNATION_EVENT`s the agent sends back:
+//!
+//! ```ignore
+//! let mut mock = MockRekuest::start().await?;
+//! registry.sync(&RekuestClient::new(mock.fakt(), "token")?, &agent, "default", true).await?;
+//! tokio::spawn(provide_forever(mock.fakt(), "token".to_string(), registry, state));
+//! mock.wait_for_agent().await;
+//! let assignation = mock.assign("rusty-image", args)?;
+//! let event = mock.next_event().await;
+//! ```
+//!
+//! The mock does not hash definitions: templates report the node hash set
+//! with `MockRekuest::set_node_hash` for their interface, or `UNHASHED`, so
+//! drift tests compare against a hash computed independently of this crate.
+//!
+//! Other subscriptions (reservations, state) are not supported.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, Notify};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use super::agent_protocol::{AgentMessage, AssignationEventMessage, Assignment, Provision};
+use super::fakt::{AgentFakt, HardwareFakt, RekuestFakt};
+use super::registry::ValueMap;
+
+/// The node hash of templates whose interface has no hash set.
+pub const UNHASHED: &str = "unhashed";
+
+#[derive(Default)]
+struct MockState {
+    instance_id: Option<String>,
+    /// Instance ids of the ensured agents; an agent's id is its index + 1.
+    agents: Vec<String>,
+    /// The templates created so far, in order.
+    templates: Vec<MockTemplate>,
+    /// Provision ids, mapped to the template id they provide.
+    provisions: HashMap<i64, String>,
+    /// Node hashes reported for templates, by interface.
+    node_hashes: HashMap<String, String>,
+    next_id: i64,
+    /// Sends messages to the connected agent.
+    agent: Option<mpsc::UnboundedSender<Message>>,
+    /// Open `assignationEvents` subscriptions, by operation id.
+    subscribers: Vec<(String, mpsc::UnboundedSender<Message>)>,
+}
+
+struct MockTemplate {
+    id: String,
+    interface: String,
+    /// The id of the agent the template belongs to.
+    agent: String,
+    extension: String,
+    hash: String,
+    params: Value,
+    dependencies: Value,
+}
+
+impl MockState {
+    fn agent_id(&mut self, instance_id: &str) -> String {
+        let index = match self.agents.iter().position(|i| i == instance_id) {
+            Some(index) => index,
+            None => {
+                self.agents.push(instance_id.to_string());
+                self.agents.len() - 1
+            }
+        };
+        (index + 1).to_string()
+    }
+
+    fn next_id(&mut self) -> i64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// Send `PROVIDE` for `template` and return the provision id.
+    fn provide(&mut self, template: &str) -> Result<i64, String> {
+        let provision = self.next_id();
+        self.provisions.insert(provision, template.to_string());
+        send(self, &AgentMessage::Provide { provision })?;
+        Ok(provision)
+    }
+
+    /// Send `ASSIGN` to `template`, providing it first if needed, and
+    /// return the assignation id.
+    fn assign(
+        &mut self,
+        template: &str,
+        args: ValueMap,
+        assignment: Assignment,
+    ) -> Result<i64, String> {
+        let existing = self
+            .provisions
+            .iter()
+            .find(|(_, t)| *t == template)
+            .map(|(id, _)| *id);
+        let provision = match existing {
+            Some(provision) => provision,
+            None => self.provide(template)?,
+        };
+
+        let assignation = self.next_id();
+        send(
+            self,
+            &AgentMessage::Assign {
+                assignation,
+                args,
+                provision,
+                assignment,
+            },
+        )?;
+        Ok(assignation)
+    }
+
+    /// Forward an event the agent sent to the `assignationEvents`
+    /// subscriptions.
+    fn publish(&mut self, event: &AssignationEventMessage) {
+        let id = self.next_id();
+        let event = json!({
+            "id": id.to_string(),
+            "kind": event.kind,
+            "returns": event.returns,
+            "message": event.message,
+            "level": null,
+            "progress": event.progress,
+            "assignation": { "id": event.assignation.to_string() },
+        });
+        self.subscribers.retain(|(operation, subscriber)| {
+            let next = json!({
+                "id": operation,
+                "type": "next",
+                "payload": { "data": { "assignationEvents": event } },
+            });
+            subscriber.send(Message::Text(next.to_string())).is_ok()
+        });
+    }
+}
+
+/// A running mock rekuest server, stopped on drop.
+pub struct MockRekuest {
+    http_addr: SocketAddr,
+    ws_addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+    connected: Arc<Notify>,
+    events: mpsc::UnboundedReceiver<AssignationEventMessage>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl MockRekuest {
+    /// Start serving on two free local ports.
+    pub async fn start() -> std::io::Result<Self> {
+        let http = TcpListener::bind("127.0.0.1:0").await?;
+        let ws = TcpListener::bind("127.0.0.1:0").await?;
+        let http_addr = http.local_addr()?;
+        let ws_addr = ws.local_addr()?;
+
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let connected = Arc::new(Notify::new());
+        let (events_tx, events) = mpsc::unbounded_channel();
+
+        let http_state = state.clone();
+        let http_task = tokio::spawn(async move {
+            while let Ok((stream, _)) = http.accept().await {
+                let state = http_state.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = serve_graphql(stream, state).await {
+                        println!("Mock rekuest failed to answer a request: {}", e);
+                    }
+                });
+            }
+        });
+
+        let ws_state = state.clone();
+        let ws_connected = connected.clone();
+        let ws_task = tokio::spawn(async move {
+            while let Ok((stream, _)) = ws.accept().await {
+                let state = ws_state.clone();
+                let connected = ws_connected.clone();
+                let events = events_tx.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = serve_websocket(stream, state, connected, events).await {
+                        println!("Mock rekuest lost a websocket: {}", e);
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            http_addr,
+            ws_addr,
+            state,
+            connected,
+            events,
+            tasks: vec![http_task, ws_task],
+        })
+    }
+
+    /// A config pointing at this server.
+    pub fn fakt(&self) -> RekuestFakt {
+        RekuestFakt {
+            endpoint_url: format!("http://{}/graphql", self.http_addr),
+            ws_endpoint_url: Some(format!("ws://{}/graphql", self.ws_addr)),
+            hardware: HardwareFakt::default(),
+            agent: AgentFakt {
+                endpoint_url: format!("ws://{}/agi", self.ws_addr),
+            },
+        }
+    }
+
+    /// Wait until an agent connected and sent its initial message.
+    pub async fn wait_for_agent(&self) {
+        if self.state.lock().unwrap().agent.is_some() {
+            return;
+        }
+        self.connected.notified().await;
+    }
+
+    /// The `(template id, interface)` of all templates created so far.
+    pub fn templates(&self) -> Vec<(String, String)> {
+        self.state
+            .lock()
+            .unwrap()
+            .templates
+            .iter()
+            .map(|t| (t.id.clone(), t.interface.clone()))
+            .collect()
+    }
+
+    /// Report `hash` as the node hash of templates for `interface`, from
+    /// the next time they are created or set.
+    pub fn set_node_hash(&self, interface: &str, hash: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .node_hashes
+            .insert(interface.to_string(), hash.to_string());
+    }
+
+    /// Send `PROVIDE` for the template registered for `interface` and
+    /// return the provision id.
+    pub fn provide(&self, interface: &str) -> Result<i64, String> {
+        let mut state = self.state.lock().unwrap();
+        let template = template_for(&state, interface)?;
+        state.provide(&template)
+    }
+
+    /// Send any `message` to the agent, e.g. an `ASSIGN` for a provision
+    /// the server does not know.
+    pub fn send(&self, message: &AgentMessage) -> Result<(), String> {
+        send(&self.state.lock().unwrap(), message)
+    }
+
+    /// Send `UNPROVIDE`.
+    pub fn unprovide(&self) -> Result<(), String> {
+        send(&self.state.lock().unwrap(), &AgentMessage::Unprovide {})
+    }
+
+    /// Send a `HEARTBEAT`; the agent answers it but emits no event.
+    pub fn heartbeat(&self) -> Result<(), String> {
+        send(&self.state.lock().unwrap(), &AgentMessage::Heartbeat)
+    }
+
+    /// Send `ASSIGN` with `args` to the template registered for `interface`,
+    /// providing it first if needed. Returns the assignation id.
+    pub fn assign(&self, interface: &str, args: ValueMap) -> Result<i64, String> {
+        self.assign_with(interface, args, Assignment::default())
+    }
+
+    /// Like `assign`, with the rest of the assign payload (reference,
+    /// `cached`, `ephemeral`, ...) set.
+    pub fn assign_with(
+        &self,
+        interface: &str,
+        args: ValueMap,
+        assignment: Assignment,
+    ) -> Result<i64, String> {
+        let mut state = self.state.lock().unwrap();
+        let template = template_for(&state, interface)?;
+        state.assign(&template, args, assignment)
+    }
+
+    /// The next `ASSIGNATION_EVENT` the agent sent, or `None` once the
+    /// server stopped.
+    pub async fn next_event(&mut self) -> Option<AssignationEventMessage> {
+        self.events.recv().await
+    }
+}
+
+impl Drop for MockRekuest {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// The id of the template registered for `interface`.
+fn template_for(state: &MockState, interface: &str) -> Result<String, String> {
+    state
+        .templates
+        .iter()
+        .find(|t| t.interface == interface)
+        .map(|t| t.id.clone())
+        .ok_or_else(|| format!("No template for {}", interface))
+}
+
+fn send(state: &MockState, message: &AgentMessage) -> Result<(), String> {
+    let agent = state.agent.as_ref().ok_or("No agent connected")?;
+    let message = serde_json::to_string(message).map_err(|e| e.to_string())?;
+    agent
+        .send(Message::Text(message))
+        .map_err(|_| "The agent disconnected".to_string())
+}
+
+/// Accept a websocket and serve it as the agent websocket, or as a GraphQL
+/// subscription if the client asked for `graphql-transport-ws`.
+// The handshake callback's error type is tungstenite's.
+#[allow(clippy::result_large_err)]
+async fn serve_websocket(
+    stream: TcpStream,
+    state: Arc<Mutex<MockState>>,
+    connected: Arc<Notify>,
+    events: mpsc::UnboundedSender<AssignationEventMessage>,
+) -> anyhow::Result<()> {
+    let mut subscription = false;
+    let ws =
+        tokio_tungstenite::accept_hdr_async(stream, |request: &Request, mut response: Response| {
+            let protocol = request.headers().get("Sec-WebSocket-Protocol");
+            if let Some(protocol) = protocol.filter(|p| *p == "graphql-transport-ws") {
+                subscription = true;
+                response
+                    .headers_mut()
+                    .insert("Sec-WebSocket-Protocol", protocol.clone());
+            }
+            Ok(response)
+        })
+        .await?;
+
+    if subscription {
+        serve_subscription(ws, state).await
+    } else {
+        serve_agent(ws, state, connected, events).await
+    }
+}
+
+/// Serve `assignationEvents` subscriptions over `graphql-transport-ws`.
+async fn serve_subscription(
+    ws: WebSocketStream<TcpStream>,
+    state: Arc<Mutex<MockState>>,
+) -> anyhow::Result<()> {
+    let (mut write, mut read) = ws.split();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+    let writer = tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            if write.send(message).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some(message) = read.next().await {
+        let text = match message? {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let message: Value = serde_json::from_str(&text)?;
+        let operation = message["id"].as_str().unwrap_or_default().to_string();
+
+        match message["type"].as_str() {
+            Some("connection_init") => {
+                tx.send(Message::Text(
+                    json!({ "type": "connection_ack" }).to_string(),
+                ))?;
+            }
+            Some("subscribe") => {
+                let query = message["payload"]["operationName"].as_str();
+                if query == Some("AssignationEvents") {
+                    state
+                        .lock()
+                        .unwrap()
+                        .subscribers
+                        .push((operation, tx.clone()));
+                } else {
+                    let error = json!({
+                        "id": operation,
+                        "type": "error",
+                        "payload": [{ "message": format!("The mock does not support {:?}", query) }],
+                    });
+                    tx.send(Message::Text(error.to_string()))?;
+                }
+            }
+            Some("complete") => break,
+            _ => {}
+        }
+    }
+
+    writer.abort();
+    Ok(())
+}
+
+async fn serve_agent(
+    ws: WebSocketStream<TcpStream>,
+    state: Arc<Mutex<MockState>>,
+    connected: Arc<Notify>,
+    events: mpsc::UnboundedSender<AssignationEventMessage>,
+) -> anyhow::Result<()> {
+    let (mut write, mut read) = ws.split();
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+    let writer = tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            if write.send(message).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some(message) = read.next().await {
+        let text = match message? {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let message: Value = serde_json::from_str(&text)?;
+
+        match message["type"].as_str() {
+            Some("INITIAL") => {
+                let instance_id = message["instance_id"].as_str().unwrap_or("default");
+                let provisions = {
+                    let mut state = state.lock().unwrap();
+                    state.instance_id = Some(instance_id.to_string());
+                    state.agent = Some(tx.clone());
+                    state
+                        .provisions
+                        .keys()
+                        .map(|id| Provision { id: id.to_string() })
+                        .collect()
+                };
+                let init = AgentMessage::Initial {
+                    instance_id: instance_id.to_string(),
+                    agent: "1".to_string(),
+                    registry: "1".to_string(),
+                    provisions,
+                    inquiries: Vec::new(),
+                };
+                tx.send(Message::Text(serde_json::to_string(&init)?))?;
+                connected.notify_one();
+            }
+            Some("ASSIGNATION_EVENT") => {
+                let event: AssignationEventMessage = serde_json::from_value(message)?;
+                state.lock().unwrap().publish(&event);
+                let _ = events.send(event);
+            }
+            _ => {}
+        }
+    }
+
+    state.lock().unwrap().agent = None;
+    writer.abort();
+    Ok(())
+}
+
+/// Answer a single GraphQL request over HTTP/1.1, then close.
+async fn serve_graphql(mut stream: TcpStream, state: Arc<Mutex<MockState>>) -> anyhow::Result<()> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            anyhow::bail!("connection closed before the request was complete");
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+        if let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end + 4;
+        }
+    };
+
+    let headers = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let content_length = headers
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    while buffer.len() < header_end + content_length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+
+    let request: Value = serde_json::from_slice(&buffer[header_end..])?;
+    let response = answer(&request, &mut state.lock().unwrap());
+    let body = response.to_string();
+
+    let head = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// The response to a GraphQL request, dispatched by operation name.
+fn answer(request: &Value, state: &mut MockState) -> Value {
+    let variables = &request["variables"];
+    let input = &variables["input"];
+
+    match request["operationName"].as_str().unwrap_or_default() {
+        "EnsureAgent" => json!({ "data": { "ensureAgent": {
+            "id": state.agent_id(input["instanceId"].as_str().unwrap_or_default()),
+            "instanceId": input["instanceId"],
+            "extensions": input["extensions"],
+            "name": input["name"],
+            "registry": { "id": "1" },
+        }}}),
+        "CreateTemplate" => {
+            let agent = state.agent_id(input["instanceId"].as_str().unwrap_or_default());
+            let extension = input["extension"].as_str().unwrap_or_default();
+            let id = create_template(state, &input["template"], agent, extension);
+            json!({ "data": { "createTemplate": { "id": id } } })
+        }
+        "CreateForeignTemplate" => {
+            let agent = input["agent"].as_str().unwrap_or_default().to_string();
+            let extension = input["extension"].as_str().unwrap_or_default();
+            let id = create_template(state, &input["template"], agent, extension);
+            json!({ "data": { "createForeignTemplate": {
+                "id": id,
+                "interface": input["template"]["interface"],
+                "node": { "id": id },
+            }}})
+        }
+        "SetExtensionTemplates" => {
+            let agent = state.agent_id(input["instanceId"].as_str().unwrap_or_default());
+            let extension = input["extension"].as_str().unwrap_or_default().to_string();
+            let templates: Vec<Value> = input["templates"]
+                .as_array()
+                .map(|templates| {
+                    templates
+                        .iter()
+                        .map(|template| {
+                            let id = create_template(state, template, agent.clone(), &extension);
+                            json!({
+                                "id": id,
+                                "interface": template["interface"],
+                                "node": { "id": id },
+                            })
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            if input["runCleanup"].as_bool().unwrap_or_default() {
+                // Delete the extension's templates that were not just set.
+                let kept: Vec<&str> = templates.iter().filter_map(|t| t["id"].as_str()).collect();
+                state.templates.retain(|t| {
+                    t.agent != agent || t.extension != extension || kept.contains(&t.id.as_str())
+                });
+                let templates = &state.templates;
+                state
+                    .provisions
+                    .retain(|_, template| templates.iter().any(|t| t.id == *template));
+            }
+            json!({ "data": { "setExtensionTemplates": templates } })
+        }
+        "AgentTemplates" => {
+            let templates: Vec<Value> = state
+                .templates
+                .iter()
+                .filter(|t| variables["agent"] == t.agent.as_str())
+                .filter(|t| variables["extension"] == t.extension.as_str())
+                .map(|t| {
+                    json!({
+                        "id": t.id,
+                        "interface": t.interface,
+                        "params": t.params,
+                        "node": { "id": t.id, "hash": t.hash },
+                        "dependencies": t.dependencies,
+                    })
+                })
+                .collect();
+            json!({ "data": { "agent": { "templates": templates } } })
+        }
+        "GetProvision" => {
+            let id = variables["id"].as_str().unwrap_or_default();
+            match id
+                .parse::<i64>()
+                .ok()
+                .and_then(|id| state.provisions.get(&id))
+            {
+                Some(template) => json!({ "data": { "provision": {
+                    "id": id,
+                    "agent": { "id": state
+                        .templates
+                        .iter()
+                        .find(|t| t.id == *template)
+                        .map_or("1", |t| t.agent.as_str()) },
+                    "template": { "id": template },
+                    "causedReservations": [],
+                }}}),
+                None => error(&format!("No provision {}", id)),
+            }
+        }
+        "Assign" => {
+            let template = input["template"].as_str().unwrap_or_default().to_string();
+            let args = input["args"].as_object().cloned().unwrap_or_default();
+            let assignment = Assignment {
+                reference: input["reference"].as_str().map(str::to_string),
+                parent: input["parent"].as_str().map(str::to_string),
+                cached: input["cached"].as_bool().unwrap_or_default(),
+                log: input["log"].as_bool().unwrap_or_default(),
+                ephemeral: input["ephemeral"].as_bool().unwrap_or_default(),
+                ..Default::default()
+            };
+            match state.assign(&template, args, assignment) {
+                Ok(assignation) => json!({ "data": { "assign": {
+                    "id": assignation.to_string(),
+                    "reference": input["reference"],
+                }}}),
+                Err(e) => error(&e),
+            }
+        }
+        "CreateHardwareRecord" => {
+            json!({ "data": { "createHardwareRecord": { "id": state.next_id().to_string() } } })
+        }
+        operation => error(&format!("The mock does not support {}", operation)),
+    }
+}
+
+/// Store `template` for `agent`, replacing one with the same interface,
+/// and return its id.
+fn create_template(
+    state: &mut MockState,
+    template: &Value,
+    agent: String,
+    extension: &str,
+) -> String {
+    let interface = template["interface"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    let hash = state
+        .node_hashes
+        .get(&interface)
+        .cloned()
+        .unwrap_or_else(|| UNHASHED.to_string());
+    let params = template["params"].clone();
+    let dependencies = template["dependencies"].clone();
+
+    let existing = state
+        .templates
+        .iter_mut()
+        .find(|t| t.interface == interface && t.agent == agent && t.extension == extension);
+    if let Some(existing) = existing {
+        existing.hash = hash;
+        existing.params = params;
+        existing.dependencies = dependencies;
+        return existing.id.clone();
+    }
+
+    let id = state.next_id().to_string();
+    state.templates.push(MockTemplate {
+        id: id.clone(),
+        interface,
+        agent,
+        extension: extension.to_string(),
+        hash,
+        params,
+        dependencies,
+    });
+    id
+}
+
+fn error(message: &str) -> Value {
+    json!({ "data": null, "errors": [{ "message": message }] })
+}
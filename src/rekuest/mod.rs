@@ -1,8 +1,27 @@
+pub mod admin;
 pub mod agent;
 pub mod agent_protocol;
 pub mod api;
+pub mod cache;
+pub mod call;
 pub mod client;
+pub mod context;
+pub mod dashboard;
 pub mod definition;
+pub mod effects;
 pub mod fakt;
+pub mod hardware;
+pub mod hash;
+pub mod local;
+pub mod manifest;
+#[cfg(feature = "mock")]
+pub mod mock;
 pub mod ports;
 pub mod registry;
+pub mod reservation;
+pub mod state;
+pub mod subscription;
+pub mod template;
+pub mod testing;
+pub mod validators;
+pub mod widgets;
@@ -1,121 +1,113 @@
+use std::marker::PhantomData;
+
 use super::api::create_template::{
     AssignWidgetInput, ChildPortInput, EffectInput, PortInput, PortKind, PortScope,
     ReturnWidgetInput, ValidatorInput,
 };
 
-/// Builder for an INT port.
-/// Required: `key`, `scope`.
-/// Optional fields can be set after `new()`.
-pub struct IntPortBuilder {
-    key: String,
-    scope: PortScope,
-    default: Option<String>,
-    description: Option<String>,
-    groups: Option<Vec<String>>,
-    effects: Option<Vec<EffectInput>>,
-    label: Option<String>,
-    assign_widget: Option<Box<Option<AssignWidgetInput>>>,
-    identifier: Option<String>,
-    nullable: bool,
-    return_widget: Option<ReturnWidgetInput>,
-    validators: Option<Vec<ValidatorInput>>,
+/// Marker types for every `PortKind`, used to specialise `PortBuilder`.
+pub mod kind {
+    pub struct Int;
+    pub struct Float;
+    pub struct Bool;
+    pub struct Str;
+    pub struct Date;
+    pub struct List;
+    pub struct Dict;
+    pub struct Structure;
+    pub struct Union;
+    pub struct Model;
 }
 
-impl IntPortBuilder {
-    pub fn new(key: &str) -> Self {
-        IntPortBuilder {
-            key: key.to_string(),
-            scope: PortScope::GLOBAL,
-            default: None,
-            description: None,
-            groups: None,
-            effects: None,
-            label: None,
-            assign_widget: None,
-            identifier: None,
-            nullable: false,
-            return_widget: None,
-            validators: None,
-        }
-    }
+/// Ties a marker type to its `PortKind` and to the Rust type its defaults
+/// are declared with.
+pub trait PortKindMarker {
+    const KIND: PortKind;
+    type Default: Into<serde_json::Value>;
+}
 
-    pub fn default(mut self, default: &str) -> Self {
-        self.default = Some(default.to_string());
-        self
-    }
+impl PortKindMarker for kind::Int {
+    const KIND: PortKind = PortKind::INT;
+    type Default = i64;
+}
 
-    pub fn description(mut self, description: &str) -> Self {
-        self.description = Some(description.to_string());
-        self
-    }
+impl PortKindMarker for kind::Float {
+    const KIND: PortKind = PortKind::FLOAT;
+    type Default = f64;
+}
 
-    pub fn groups(mut self, groups: Vec<&str>) -> Self {
-        self.groups = Some(groups.into_iter().map(|g| g.to_string()).collect());
-        self
-    }
+impl PortKindMarker for kind::Bool {
+    const KIND: PortKind = PortKind::BOOL;
+    type Default = bool;
+}
 
-    pub fn effects(mut self, effects: Vec<EffectInput>) -> Self {
-        self.effects = Some(effects);
-        self
-    }
+impl PortKindMarker for kind::Str {
+    const KIND: PortKind = PortKind::STRING;
+    type Default = String;
+}
 
-    pub fn label(mut self, label: &str) -> Self {
-        self.label = Some(label.to_string());
-        self
-    }
+/// Defaults are ISO 8601 strings, e.g. `2024-01-01T00:00:00Z`.
+impl PortKindMarker for kind::Date {
+    const KIND: PortKind = PortKind::DATE;
+    type Default = String;
+}
 
-    pub fn assign_widget(mut self, widget: Option<AssignWidgetInput>) -> Self {
-        self.assign_widget = Some(Box::new(widget));
-        self
-    }
+impl PortKindMarker for kind::List {
+    const KIND: PortKind = PortKind::LIST;
+    type Default = Vec<serde_json::Value>;
+}
 
-    pub fn identifier(mut self, identifier: &str) -> Self {
-        self.identifier = Some(identifier.to_string());
-        self
-    }
+impl PortKindMarker for kind::Dict {
+    const KIND: PortKind = PortKind::DICT;
+    type Default = serde_json::Map<String, serde_json::Value>;
+}
 
-    pub fn nullable(mut self, nullable: bool) -> Self {
-        self.nullable = nullable;
-        self
-    }
+/// Defaults are the id of the structure on its home service.
+impl PortKindMarker for kind::Structure {
+    const KIND: PortKind = PortKind::STRUCTURE;
+    type Default = String;
+}
 
-    pub fn return_widget(mut self, widget: ReturnWidgetInput) -> Self {
-        self.return_widget = Some(widget);
-        self
-    }
+impl PortKindMarker for kind::Union {
+    const KIND: PortKind = PortKind::UNION;
+    type Default = serde_json::Value;
+}
 
-    pub fn validators(mut self, validators: Vec<ValidatorInput>) -> Self {
-        self.validators = Some(validators);
-        self
-    }
+impl PortKindMarker for kind::Model {
+    const KIND: PortKind = PortKind::MODEL;
+    type Default = serde_json::Map<String, serde_json::Value>;
+}
 
-    pub fn build(self) -> PortInput {
-        PortInput {
-            key: self.key,
-            default: self.default,
-            scope: self.scope,
-            kind: PortKind::INT,
-            children: Some(vec![]),
-            description: self.description,
-            groups: self.groups,
-            effects: self.effects,
-            label: self.label,
-            assign_widget: self.assign_widget.unwrap_or_else(|| Box::new(None)),
-            identifier: self.identifier,
-            nullable: self.nullable,
-            return_widget: self.return_widget,
-            validators: self.validators,
-        }
-    }
+/// Where a `PortBuilder` can be used. `ChildPortInput` has no validators or
+/// groups, so setting either turns a builder into a `TopLevel` one, which
+/// can no longer describe a child port.
+pub mod place {
+    pub struct Anywhere;
+    pub struct TopLevel;
+}
+
+/// Conversion of a port builder into a `ChildPortInput`, used to describe the
+/// children of LIST, DICT, UNION and MODEL ports.
+///
+/// Only builders without validators or groups are children:
+///
+/// ```compile_fail
+/// use arkirust::rekuest::ports::Port;
+/// use arkirust::rekuest::validators::Validator;
+///
+/// Port::new_list("sizes", Port::new_int("item").validator(Validator::min(0)));
+/// ```
+pub trait IntoChildPort {
+    fn build_child(self) -> ChildPortInput;
 }
 
-/// Builder for a STRING port.
-/// `scope` is always `GLOBAL`, so we do not require it in the constructor.
-/// Required: `key`.
-pub struct StringPortBuilder {
+/// Builder for a port of kind `K`.
+/// Required: `key` (and whatever the kind needs, see the `Port` constructors).
+/// Optional fields can be set after construction.
+pub struct PortBuilder<K: PortKindMarker, P = place::Anywhere> {
     key: String,
     scope: PortScope,
-    default: Option<String>,
+    default: Option<serde_json::Value>,
     description: Option<String>,
     groups: Option<Vec<String>>,
     effects: Option<Vec<EffectInput>>,
@@ -125,11 +117,24 @@ pub struct StringPortBuilder {
     nullable: bool,
     return_widget: Option<ReturnWidgetInput>,
     validators: Option<Vec<ValidatorInput>>,
+    children: Vec<ChildPortInput>,
+    kind: PhantomData<(K, P)>,
 }
 
-impl StringPortBuilder {
-    pub fn new(key: &str) -> Self {
-        StringPortBuilder {
+pub type IntPortBuilder = PortBuilder<kind::Int>;
+pub type FloatPortBuilder = PortBuilder<kind::Float>;
+pub type BoolPortBuilder = PortBuilder<kind::Bool>;
+pub type StringPortBuilder = PortBuilder<kind::Str>;
+pub type DatePortBuilder = PortBuilder<kind::Date>;
+pub type ListPortBuilder = PortBuilder<kind::List>;
+pub type DictPortBuilder = PortBuilder<kind::Dict>;
+pub type StructurePortBuilder = PortBuilder<kind::Structure>;
+pub type UnionPortBuilder = PortBuilder<kind::Union>;
+pub type ModelPortBuilder = PortBuilder<kind::Model>;
+
+impl<K: PortKindMarker> PortBuilder<K> {
+    fn with_key(key: &str) -> Self {
+        PortBuilder {
             key: key.to_string(),
             scope: PortScope::GLOBAL,
             default: None,
@@ -142,118 +147,39 @@ impl StringPortBuilder {
             nullable: false,
             return_widget: None,
             validators: None,
+            children: Vec::new(),
+            kind: PhantomData,
         }
     }
+}
 
-    pub fn default(mut self, default: &str) -> Self {
-        self.default = Some(default.to_string());
-        self
-    }
-
-    pub fn description(mut self, description: &str) -> Self {
-        self.description = Some(description.to_string());
-        self
-    }
-
-    pub fn groups(mut self, groups: Vec<&str>) -> Self {
-        self.groups = Some(groups.into_iter().map(|g| g.to_string()).collect());
-        self
-    }
-
-    pub fn effects(mut self, effects: Vec<EffectInput>) -> Self {
-        self.effects = Some(effects);
-        self
-    }
-
-    pub fn label(mut self, label: &str) -> Self {
-        self.label = Some(label.to_string());
-        self
-    }
-
-    pub fn assign_widget(mut self, widget: Option<AssignWidgetInput>) -> Self {
-        self.assign_widget = Some(Box::new(widget));
-        self
-    }
-
-    pub fn identifier(mut self, identifier: &str) -> Self {
-        self.identifier = Some(identifier.to_string());
-        self
-    }
-
-    pub fn nullable(mut self, nullable: bool) -> Self {
-        self.nullable = nullable;
-        self
-    }
-
-    pub fn return_widget(mut self, widget: ReturnWidgetInput) -> Self {
-        self.return_widget = Some(widget);
-        self
-    }
-
-    pub fn validators(mut self, validators: Vec<ValidatorInput>) -> Self {
-        self.validators = Some(validators);
-        self
-    }
-
-    pub fn build(self) -> PortInput {
-        PortInput {
+impl<K: PortKindMarker, P> PortBuilder<K, P> {
+    fn top_level(self) -> PortBuilder<K, place::TopLevel> {
+        PortBuilder {
             key: self.key,
+            scope: self.scope,
             default: self.default,
-            scope: PortScope::GLOBAL, // Always global
-            kind: PortKind::STRING,
-            children: Some(vec![]),
             description: self.description,
             groups: self.groups,
             effects: self.effects,
             label: self.label,
-            assign_widget: self.assign_widget.unwrap_or_else(|| Box::new(None)),
+            assign_widget: self.assign_widget,
             identifier: self.identifier,
             nullable: self.nullable,
             return_widget: self.return_widget,
             validators: self.validators,
+            children: self.children,
+            kind: PhantomData,
         }
     }
-}
 
-/// Builder for a LIST port.
-/// Required: `key`, `scope`.
-pub struct ListPortBuilder {
-    key: String,
-    scope: PortScope,
-    default: Option<String>,
-    description: Option<String>,
-    groups: Option<Vec<String>>,
-    effects: Option<Vec<EffectInput>>,
-    label: Option<String>,
-    assign_widget: Option<Box<Option<AssignWidgetInput>>>,
-    identifier: Option<String>,
-    nullable: bool,
-    return_widget: Option<ReturnWidgetInput>,
-    validators: Option<Vec<ValidatorInput>>,
-    children: Option<Vec<ChildPortInput>>,
-}
-
-impl ListPortBuilder {
-    pub fn new(key: &str, child: ChildPortInput) -> Self {
-        ListPortBuilder {
-            key: key.to_string(),
-            scope: PortScope::GLOBAL,
-            default: None,
-            description: None,
-            groups: None,
-            effects: None,
-            label: None,
-            assign_widget: None,
-            identifier: None,
-            nullable: false,
-            return_widget: None,
-            validators: None,
-            children: Some(vec![child]),
-        }
+    pub fn default(mut self, default: impl Into<K::Default>) -> Self {
+        self.default = Some(default.into().into());
+        self
     }
 
-    pub fn default(mut self, default: &str) -> Self {
-        self.default = Some(default.to_string());
+    pub fn scope(mut self, scope: PortScope) -> Self {
+        self.scope = scope;
         self
     }
 
@@ -262,9 +188,10 @@ impl ListPortBuilder {
         self
     }
 
-    pub fn groups(mut self, groups: Vec<&str>) -> Self {
-        self.groups = Some(groups.into_iter().map(|g| g.to_string()).collect());
-        self
+    pub fn groups(self, groups: Vec<&str>) -> PortBuilder<K, place::TopLevel> {
+        let mut builder = self.top_level();
+        builder.groups = Some(groups.into_iter().map(|g| g.to_string()).collect());
+        builder
     }
 
     pub fn effects(mut self, effects: Vec<EffectInput>) -> Self {
@@ -272,13 +199,21 @@ impl ListPortBuilder {
         self
     }
 
+    /// Add a single effect, e.g. `Effect::message("Ignored").when("mode").is("simple")`.
+    pub fn effect(mut self, effect: impl Into<EffectInput>) -> Self {
+        self.effects
+            .get_or_insert_with(Vec::new)
+            .push(effect.into());
+        self
+    }
+
     pub fn label(mut self, label: &str) -> Self {
         self.label = Some(label.to_string());
         self
     }
 
-    pub fn assign_widget(mut self, widget: Option<AssignWidgetInput>) -> Self {
-        self.assign_widget = Some(Box::new(widget));
+    pub fn assign_widget(mut self, widget: AssignWidgetInput) -> Self {
+        self.assign_widget = Some(Box::new(Some(widget)));
         self
     }
 
@@ -297,9 +232,23 @@ impl ListPortBuilder {
         self
     }
 
-    pub fn validators(mut self, validators: Vec<ValidatorInput>) -> Self {
-        self.validators = Some(validators);
-        self
+    pub fn validators(self, validators: Vec<ValidatorInput>) -> PortBuilder<K, place::TopLevel> {
+        let mut builder = self.top_level();
+        builder.validators = Some(validators);
+        builder
+    }
+
+    /// Add a single validator, e.g. `Validator::range(0, 100)`.
+    pub fn validator(
+        self,
+        validator: impl Into<ValidatorInput>,
+    ) -> PortBuilder<K, place::TopLevel> {
+        let mut builder = self.top_level();
+        builder
+            .validators
+            .get_or_insert_with(Vec::new)
+            .push(validator.into());
+        builder
     }
 
     pub fn build(self) -> PortInput {
@@ -307,8 +256,8 @@ impl ListPortBuilder {
             key: self.key,
             default: self.default,
             scope: self.scope,
-            kind: PortKind::LIST,
-            children: self.children,
+            kind: K::KIND,
+            children: Some(self.children),
             description: self.description,
             groups: self.groups,
             effects: self.effects,
@@ -322,127 +271,127 @@ impl ListPortBuilder {
     }
 }
 
-pub struct StructurePortBuilder {
-    key: String,
-    scope: PortScope,
-    default: Option<String>,
-    description: Option<String>,
-    groups: Option<Vec<String>>,
-    effects: Option<Vec<EffectInput>>,
-    label: Option<String>,
-    assign_widget: Option<Box<Option<AssignWidgetInput>>>,
-    identifier: Option<String>,
-    nullable: bool,
-    return_widget: Option<ReturnWidgetInput>,
-    validators: Option<Vec<ValidatorInput>>,
-    children: Option<Vec<ChildPortInput>>,
-}
-
-impl StructurePortBuilder {
-    pub fn new(key: &str, identifier: &str) -> Self {
-        StructurePortBuilder {
-            key: key.to_string(),
-            scope: PortScope::GLOBAL,
-            default: None,
-            description: None,
-            groups: None,
-            effects: None,
-            label: None,
-            assign_widget: None,
-            identifier: Some(identifier.to_string()),
-            nullable: false,
-            return_widget: None,
-            validators: None,
-            children: Some(vec![]),
+impl<K: PortKindMarker> IntoChildPort for PortBuilder<K> {
+    fn build_child(self) -> ChildPortInput {
+        ChildPortInput {
+            key: self.key,
+            default: self.default,
+            scope: self.scope,
+            kind: K::KIND,
+            children: Some(self.children),
+            description: self.description,
+            effects: self.effects,
+            label: self.label,
+            assign_widget: self.assign_widget.unwrap_or_else(|| Box::new(None)),
+            identifier: self.identifier,
+            nullable: self.nullable,
+            return_widget: self.return_widget,
         }
     }
+}
 
-    pub fn default(mut self, default: &str) -> Self {
-        self.default = Some(default.to_string());
+impl<P> PortBuilder<kind::Union, P> {
+    /// Add a variant to the union.
+    pub fn variant(mut self, variant: impl IntoChildPort) -> Self {
+        self.children.push(variant.build_child());
         self
     }
+}
 
-    pub fn description(mut self, description: &str) -> Self {
-        self.description = Some(description.to_string());
+impl<P> PortBuilder<kind::Model, P> {
+    /// Add a field to the model.
+    pub fn field(mut self, field: impl IntoChildPort) -> Self {
+        self.children.push(field.build_child());
         self
     }
+}
 
-    pub fn groups(mut self, groups: Vec<&str>) -> Self {
-        self.groups = Some(groups.into_iter().map(|g| g.to_string()).collect());
-        self
-    }
+pub struct Port {}
 
-    pub fn effects(mut self, effects: Vec<EffectInput>) -> Self {
-        self.effects = Some(effects);
-        self
+impl Port {
+    pub fn new_int(key: &str) -> IntPortBuilder {
+        PortBuilder::with_key(key)
     }
 
-    pub fn label(mut self, label: &str) -> Self {
-        self.label = Some(label.to_string());
-        self
+    pub fn new_string(key: &str) -> StringPortBuilder {
+        PortBuilder::with_key(key)
     }
 
-    pub fn assign_widget(mut self, widget: Option<AssignWidgetInput>) -> Self {
-        self.assign_widget = Some(Box::new(widget));
-        self
+    pub fn new_float(key: &str) -> FloatPortBuilder {
+        PortBuilder::with_key(key)
     }
 
-    pub fn identifier(mut self, identifier: &str) -> Self {
-        self.identifier = Some(identifier.to_string());
-        self
+    pub fn new_bool(key: &str) -> BoolPortBuilder {
+        PortBuilder::with_key(key)
     }
 
-    pub fn nullable(mut self, nullable: bool) -> Self {
-        self.nullable = nullable;
-        self
-    }
-
-    pub fn return_widget(mut self, widget: ReturnWidgetInput) -> Self {
-        self.return_widget = Some(widget);
-        self
+    pub fn new_date(key: &str) -> DatePortBuilder {
+        PortBuilder::with_key(key)
     }
 
-    pub fn validators(mut self, validators: Vec<ValidatorInput>) -> Self {
-        self.validators = Some(validators);
-        self
+    /// A LIST port whose items are described by `child`.
+    pub fn new_list(key: &str, child: impl IntoChildPort) -> ListPortBuilder {
+        let mut builder = PortBuilder::with_key(key);
+        builder.children.push(child.build_child());
+        builder
     }
 
-    pub fn build(self) -> PortInput {
-        PortInput {
-            key: self.key,
-            default: self.default,
-            scope: self.scope,
-            kind: PortKind::STRUCTURE,
-            children: self.children,
-            description: self.description,
-            groups: self.groups,
-            effects: self.effects,
-            label: self.label,
-            assign_widget: self.assign_widget.unwrap_or_else(|| Box::new(None)),
-            identifier: self.identifier,
-            nullable: self.nullable,
-            return_widget: self.return_widget,
-            validators: self.validators,
-        }
+    /// A DICT port with string keys whose values are described by `child`.
+    pub fn new_dict(key: &str, child: impl IntoChildPort) -> DictPortBuilder {
+        let mut builder = PortBuilder::with_key(key);
+        builder.children.push(child.build_child());
+        builder
     }
-}
 
-pub struct Port {}
-
-impl Port {
-    pub fn new_int(key: &str) -> IntPortBuilder {
-        IntPortBuilder::new(key)
+    pub fn new_structure(key: &str, identifier: &str) -> StructurePortBuilder {
+        PortBuilder::with_key(key).identifier(identifier)
     }
 
-    pub fn new_string(key: &str) -> StringPortBuilder {
-        StringPortBuilder::new(key)
+    /// A UNION port, add its variants with `variant()`.
+    pub fn new_union(key: &str) -> UnionPortBuilder {
+        PortBuilder::with_key(key)
     }
 
-    pub fn new_list(key: &str, child: ChildPortInput) -> ListPortBuilder {
-        ListPortBuilder::new(key, child)
+    /// A MODEL port, add its fields with `field()`.
+    pub fn new_model(key: &str, identifier: &str) -> ModelPortBuilder {
+        PortBuilder::with_key(key).identifier(identifier)
     }
+}
 
-    pub fn new_structure(key: &str, identifier: &str) -> StructurePortBuilder {
-        StructurePortBuilder::new(key, identifier)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rekuest::effects::Effect;
+    use crate::rekuest::validators::Validator;
+
+    #[test]
+    fn children_keep_their_fields() {
+        let port = Port::new_list(
+            "sizes",
+            Port::new_int("item")
+                .default(3)
+                .description("A size")
+                .effect(Effect::message("Too big").when("mode").is("small")),
+        )
+        .build();
+
+        let child = &port.children.unwrap()[0];
+        assert_eq!(child.key, "item");
+        assert_eq!(child.default, Some(serde_json::json!(3)));
+        assert_eq!(child.description.as_deref(), Some("A size"));
+        assert_eq!(child.effects.as_ref().map(Vec::len), Some(1));
+    }
+
+    #[test]
+    fn top_level_ports_keep_validators_and_groups() {
+        let port = Port::new_int("count")
+            .validator(Validator::min(0))
+            .groups(vec!["advanced"])
+            .nullable(true)
+            .build();
+
+        assert_eq!(port.validators.map(|v| v.len()), Some(1));
+        assert_eq!(port.groups, Some(vec!["advanced".to_string()]));
+        assert!(port.nullable);
     }
 }
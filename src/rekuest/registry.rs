@@ -1,55 +1,915 @@
-use crate::App;
-
+use super::api::agent_templates;
+use super::api::create_foreign_template;
+use super::api::create_foreign_template_vars;
 use super::api::create_template;
+use super::api::create_template::NodeKind;
+use super::api::ensure_agent::EnsureAgentEnsureAgent;
+use super::api::set_extension_templates;
+use super::api::set_extension_templates_vars;
+use super::api::AgentTemplates;
+use super::cache::ResultCache;
+use super::client::RekuestClient;
+use super::context::Context;
+use super::definition::check_definition;
+use super::definition::Definition;
+use super::definition::InvalidDefinition;
+use super::hash::hash_definition;
+use super::ports::Port;
+use super::template::Template;
+use super::testing::report_test_result;
+use super::testing::NodeTest;
+use super::testing::SyncedTemplate;
+use super::testing::TestOutcome;
+use graphql_client::GraphQLQuery;
+use graphql_client::QueryBody;
+use graphql_client::Response;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::any::TypeId;
 use std::collections::HashMap;
+use std::fmt;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::RwLock;
+
+/// Arguments and returns of an assignation, keyed by port key.
+pub type ValueMap = serde_json::Map<String, serde_json::Value>;
+
+pub type FunctionFuture = Pin<Box<dyn Future<Output = Result<ValueMap, FunctionError>> + Send>>;
+
+/// A registered function, called with the `Context` of an assignation and
+/// its raw arguments.
+pub type RegisteredFunction<S> = Box<dyn Fn(Context<S>, ValueMap) -> FunctionFuture + Send + Sync>;
+
+/// Why a registered function did not produce returns.
+#[derive(Debug)]
+pub enum FunctionError {
+    /// The argument for port `key` is missing or has the wrong shape.
+    InvalidArg { key: String, message: String },
+    /// The returns did not serialize into a map of port keys.
+    InvalidReturns(String),
+    /// The function itself returned an error.
+    Failed(String),
+}
+
+impl fmt::Display for FunctionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FunctionError::InvalidArg { key, message } => {
+                write!(f, "Invalid argument {}: {}", key, message)
+            }
+            FunctionError::InvalidReturns(message) => write!(f, "Invalid returns: {}", message),
+            FunctionError::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
 
-pub struct FunctionRegistry {
-    functions: HashMap<
-        String,
-        Box<dyn Fn((App, String)) -> Pin<Box<dyn Future<Output = String> + Send>> + Send + Sync>,
-    >,
+impl std::error::Error for FunctionError {}
+
+/// How a registered template differs from its counterpart on the server.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateDrift {
+    /// Registered locally, but not on the server.
+    Missing { interface: String },
+    /// On both, but the definitions hash differently.
+    Changed {
+        interface: String,
+        local: String,
+        server: String,
+    },
+    /// On both with the same definition, but other dependencies, params,
+    /// logo or `dynamic`.
+    Reconfigured { interface: String },
+    /// On the server, but no longer registered locally.
+    Stale { interface: String },
+}
+
+impl fmt::Display for TemplateDrift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateDrift::Missing { interface } => {
+                write!(f, "+ {} (not on the server)", interface)
+            }
+            TemplateDrift::Changed {
+                interface,
+                local,
+                server,
+            } => write!(f, "~ {} (local {}, server {})", interface, local, server),
+            TemplateDrift::Reconfigured { interface } => {
+                write!(
+                    f,
+                    "~ {} (same definition, other dependencies or params)",
+                    interface
+                )
+            }
+            TemplateDrift::Stale { interface } => write!(f, "- {} (only on the server)", interface),
+        }
+    }
+}
+
+/// The functions an agent provides. `S` is the application state reachable
+/// from every function's `Context`, e.g. a struct holding the service
+/// clients it needs.
+pub struct FunctionRegistry<S> {
+    functions: HashMap<String, Arc<RegisteredFunction<S>>>,
     templates: HashMap<String, create_template::TemplateInput>,
+    /// Server side template ids, mapped to the interface they were synced for.
+    template_ids: HashMap<String, String>,
+    /// Server side ids of the synced templates, keyed by interface. Shared
+    /// with the tester functions, which report their results.
+    synced: Arc<RwLock<HashMap<String, SyncedTemplate>>>,
+    tests: Vec<RegisteredTest>,
+    /// Result caches of the templates registered as pure, keyed by interface.
+    caches: HashMap<String, Arc<ResultCache>>,
+    /// Interfaces keyed by the type of the registered function, see
+    /// `interface_of`.
+    interfaces: HashMap<TypeId, String>,
 }
 
-impl FunctionRegistry {
+/// A `NodeTest` added with `FunctionRegistry::add_test`.
+struct RegisteredTest {
+    /// The interface of the tested template.
+    interface: String,
+    /// The interface of the tester template running the test.
+    tester: String,
+    name: String,
+    description: String,
+    test: Arc<NodeTest>,
+}
+
+impl<S: Clone + Send + Sync + 'static> Default for FunctionRegistry<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: Clone + Send + Sync + 'static> FunctionRegistry<S> {
     pub fn new() -> Self {
         Self {
             functions: HashMap::new(),
             templates: HashMap::new(),
+            template_ids: HashMap::new(),
+            synced: Arc::new(RwLock::new(HashMap::new())),
+            tests: Vec::new(),
+            caches: HashMap::new(),
+            interfaces: HashMap::new(),
         }
     }
 
-    pub fn register<F, Fut>(
+    /// Register `function` as the implementation of `template`, keyed by the
+    /// template's interface. Arguments are deserialized into `A` and returns
+    /// serialized from `R` here, so functions only deal with their own types.
+    ///
+    /// The definition is validated first (see `Definition::try_build`), an
+    /// invalid one is not registered.
+    pub fn register<F, Fut, A, R, E>(
         &mut self,
-        name: &str,
-        function: F,
         template: create_template::TemplateInput,
-    ) where
-        F: Fn(App, String) -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = String> + Send + 'static,
+        function: F,
+    ) -> Result<(), InvalidDefinition>
+    where
+        F: Fn(Context<S>, A) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<R, E>> + Send + 'static,
+        A: DeserializeOwned + Send + 'static,
+        R: Serialize,
+        E: fmt::Display,
     {
-        // Wrap the given function into one returning a boxed, pinned future
-        let wrapped =
-            move |(app, input): (App, String)| -> Pin<Box<dyn Future<Output = String> + Send>> {
-                Box::pin(function(app, input))
+        check_definition(&template.definition)?;
+
+        let required: Vec<String> = template
+            .definition
+            .args
+            .iter()
+            .filter(|port| !port.nullable && port.default.is_none())
+            .map(|port| port.key.clone())
+            .collect();
+
+        let wrapped = move |context: Context<S>, args: ValueMap| -> FunctionFuture {
+            let args = match deserialize_args::<A>(&required, args) {
+                Ok(args) => args,
+                Err(e) => return Box::pin(async move { Err(e) }),
             };
+            let returns = function(context, args);
+
+            Box::pin(async move {
+                let returns = returns
+                    .await
+                    .map_err(|e| FunctionError::Failed(e.to_string()))?;
+                match serde_json::to_value(returns) {
+                    Ok(serde_json::Value::Object(map)) => Ok(map),
+                    Ok(serde_json::Value::Null) => Ok(ValueMap::new()),
+                    Ok(other) => Err(FunctionError::InvalidReturns(format!(
+                        "expected a map of port keys, got {}",
+                        other
+                    ))),
+                    Err(e) => Err(FunctionError::InvalidReturns(e.to_string())),
+                }
+            })
+        };
 
-        self.functions.insert(name.to_string(), Box::new(wrapped));
-        self.templates.insert(name.to_string(), template);
+        let interface = template.interface.clone();
+        self.interfaces.insert(TypeId::of::<F>(), interface.clone());
+        self.functions
+            .insert(interface.clone(), Arc::new(Box::new(wrapped)));
+        self.templates.insert(interface, template);
+        Ok(())
     }
 
-    pub fn get_function(
-        &self,
+    /// The interface `function` was registered for. Every function item has
+    /// its own type, so this finds e.g. `register(template, my_function)`
+    /// given `&my_function`. If a function was registered more than once,
+    /// the last interface wins.
+    pub fn interface_of<F: 'static>(&self, _function: &F) -> Option<&str> {
+        self.interfaces.get(&TypeId::of::<F>()).map(String::as_str)
+    }
+
+    /// Cache up to `capacity` results of the function registered for
+    /// `interface`. Only for pure functions: an assignation made with
+    /// `cached` and arguments seen before gets the earlier returns without
+    /// running the function. Returns of `ephemeral` assignations are not
+    /// stored.
+    pub fn cache_results(
+        &mut self,
+        interface: &str,
+        capacity: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.functions.contains_key(interface) {
+            return Err(format!("No function registered for {}", interface).into());
+        }
+        self.caches
+            .insert(interface.to_string(), Arc::new(ResultCache::new(capacity)));
+        Ok(())
+    }
+
+    /// The result cache of `interface`, if enabled with `cache_results`.
+    pub fn cache_for(&self, interface: &str) -> Option<&ResultCache> {
+        self.caches.get(interface).map(Arc::as_ref)
+    }
+
+    /// Attach `test` to the template registered for `interface`, usually
+    /// through the `register` function generated by `#[test_for]`.
+    ///
+    /// The test is also registered as a tester template (its definition
+    /// `is_test_for` the tested node), so it can be run on demand through
+    /// the agent. Either way the result is reported with `createTestResult`.
+    pub fn add_test(
+        &mut self,
+        interface: &str,
         name: &str,
-    ) -> Option<
-        &Box<dyn Fn((App, String)) -> Pin<Box<dyn Future<Output = String> + Send>> + Send + Sync>,
-    > {
-        self.functions.get(name)
+        description: &str,
+        test: NodeTest,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let function = match self.functions.get(interface) {
+            Some(function) => function.clone(),
+            None => return Err(format!("No function registered for {}", interface).into()),
+        };
+        let tested_hash = self.hash(interface).unwrap_or_default();
+
+        let tester = format!("{}.test.{}", interface, name);
+        let definition = Definition::new(&format!("Test {}", name), NodeKind::FUNCTION)
+            .description(if description.is_empty() {
+                name
+            } else {
+                description
+            })
+            .is_test_for(vec![&tested_hash])
+            .returns(vec![
+                Port::new_bool("passed").build(),
+                Port::new_string("result").build(),
+            ])
+            .try_build()?;
+
+        let test = Arc::new(test);
+        let synced = self.synced.clone();
+        let (tested, tester_interface, test_name, test_description) = (
+            interface.to_string(),
+            tester.clone(),
+            name.to_string(),
+            description.to_string(),
+        );
+        let run_test = test.clone();
+        let wrapped = move |context: Context<S>, _args: ValueMap| -> FunctionFuture {
+            let function = function.clone();
+            let test = run_test.clone();
+            let ids = {
+                let synced = synced.read().unwrap();
+                synced
+                    .get(&tested)
+                    .cloned()
+                    .zip(synced.get(&tester_interface).cloned())
+            };
+            let (name, description) = (test_name.clone(), test_description.clone());
+
+            Box::pin(async move {
+                let client = context.client().cloned();
+                let result = test.run(&function, context).await;
+                match client.zip(ids) {
+                    Some((client, (tested, tester))) => {
+                        if let Err(e) = report_test_result(
+                            &client,
+                            &tested,
+                            &tester,
+                            &name,
+                            &description,
+                            &result,
+                        )
+                        .await
+                        {
+                            println!("Failed to report test {}: {}", name, e);
+                        }
+                    }
+                    None => println!("Test {} ran without synced templates, not reporting", name),
+                }
+                Ok(result.into_returns())
+            })
+        };
+
+        self.functions
+            .insert(tester.clone(), Arc::new(Box::new(wrapped)));
+        self.templates
+            .insert(tester.clone(), Template::new(&tester, definition).build());
+        self.tests.push(RegisteredTest {
+            interface: interface.to_string(),
+            tester,
+            name: name.to_string(),
+            description: description.to_string(),
+            test,
+        });
+        Ok(())
+    }
+
+    /// Run all added tests locally, in a `Context` made from `state` and
+    /// `client`.
+    pub async fn run_tests(&self, state: S, client: &RekuestClient) -> Vec<TestOutcome> {
+        let mut outcomes = Vec::new();
+        for test in &self.tests {
+            let function = &self.functions[&test.interface];
+            let context = Context::new(state.clone(), "local-test", client.clone(), HashMap::new());
+            outcomes.push(TestOutcome {
+                interface: test.interface.clone(),
+                name: test.name.clone(),
+                result: test.test.run(function, context).await,
+            });
+        }
+        outcomes
+    }
+
+    /// Report `outcomes` of `run_tests` with `createTestResult`. The
+    /// registry has to be synced first.
+    pub async fn report_tests(
+        &self,
+        client: &RekuestClient,
+        outcomes: &[TestOutcome],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for outcome in outcomes {
+            let test = self
+                .tests
+                .iter()
+                .find(|t| t.interface == outcome.interface && t.name == outcome.name)
+                .ok_or_else(|| format!("Unknown test {}", outcome.name))?;
+            let (tested, tester) = {
+                let synced = self.synced.read().unwrap();
+                match (synced.get(&test.interface), synced.get(&test.tester)) {
+                    (Some(tested), Some(tester)) => (tested.clone(), tester.clone()),
+                    _ => return Err(format!("Test {} is not synced", test.name).into()),
+                }
+            };
+            report_test_result(
+                client,
+                &tested,
+                &tester,
+                &test.name,
+                &test.description,
+                &outcome.result,
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// The `NodeHash` of the template registered for `interface`.
+    pub fn hash(&self, interface: &str) -> Option<String> {
+        self.templates
+            .get(interface)
+            .map(|template| hash_definition(&template.definition))
+    }
+
+    /// Compare the registered templates with those the agent with id `agent`
+    /// has on the server for `extension`: definitions by hash, params and
+    /// dependencies field by field.
+    pub async fn drift(
+        &self,
+        client: &RekuestClient,
+        agent: &str,
+        extension: &str,
+    ) -> Result<Vec<TemplateDrift>, Box<dyn std::error::Error>> {
+        let server = server_templates(client, agent, extension).await?;
+        Ok(self.compare(&server))
     }
 
-    pub fn get_template(&self, name: &str) -> Option<&create_template::TemplateInput> {
-        self.templates.get(name)
+    fn compare(
+        &self,
+        server: &[agent_templates::AgentTemplatesAgentTemplates],
+    ) -> Vec<TemplateDrift> {
+        let local: Vec<(String, String)> = self
+            .templates
+            .iter()
+            .map(|(interface, template)| (interface.clone(), hash_definition(&template.definition)))
+            .collect();
+        let hashes: Vec<(String, String)> = server
+            .iter()
+            .map(|t| (t.interface.clone(), t.node.hash.clone()))
+            .collect();
+        let mut drift = compare_hashes(&local, &hashes);
+
+        for server in server {
+            let Some(template) = self.templates.get(&server.interface) else {
+                continue;
+            };
+            if hash_definition(&template.definition) != server.node.hash {
+                continue;
+            }
+            let template = serde_json::to_value(template).unwrap_or_default();
+            let dependencies = serde_json::to_value(&server.dependencies).unwrap_or_default();
+            if !matches_server(&template, &server.params, &dependencies) {
+                drift.push(TemplateDrift::Reconfigured {
+                    interface: server.interface.clone(),
+                });
+            }
+        }
+        drift
+    }
+
+    /// Push all registered templates to the server with a single
+    /// `setExtensionTemplates` call and remember the returned template ids.
+    /// With `run_cleanup` the server deletes templates of this extension
+    /// that are no longer registered.
+    ///
+    /// If the server already holds exactly these templates (see `drift`)
+    /// the push is skipped and the existing template ids are used.
+    pub async fn sync(
+        &mut self,
+        client: &RekuestClient,
+        agent: &EnsureAgentEnsureAgent,
+        extension: &str,
+        run_cleanup: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let server = server_templates(client, &agent.id, extension).await?;
+        let unchanged = self.compare(&server).iter().all(|drift| match drift {
+            TemplateDrift::Stale { .. } => !run_cleanup,
+            _ => false,
+        });
+
+        if unchanged {
+            println!("Templates of {} are up to date", extension);
+            self.remember(
+                server
+                    .into_iter()
+                    .map(|t| (t.id, t.interface, t.node.id))
+                    .collect(),
+            );
+            return Ok(());
+        }
+
+        let request = QueryBody {
+            variables: set_extension_templates_vars::Variables {
+                input: set_extension_templates_vars::SetExtensionTemplatesInput {
+                    templates: self.templates.values().cloned().collect(),
+                    instance_id: agent.instance_id.clone(),
+                    extension: extension.to_string(),
+                    run_cleanup,
+                },
+            },
+            query: set_extension_templates::QUERY,
+            operation_name: set_extension_templates::OPERATION_NAME,
+        };
+
+        let response: Response<set_extension_templates::ResponseData> =
+            client.request(&request).send().await?.json().await?;
+
+        let templates = match response.data {
+            Some(data) => data.set_extension_templates,
+            None => {
+                return Err(format!("setExtensionTemplates failed: {:?}", response.errors).into());
+            }
+        };
+
+        self.remember(
+            templates
+                .into_iter()
+                .map(|t| (t.id, t.interface, t.node.id))
+                .collect(),
+        );
+
+        Ok(())
+    }
+
+    /// Register all templates on behalf of the agent with id `agent`, e.g.
+    /// an instrument this agent is a gateway for, under `extension`. Their
+    /// assignations reach this agent; serve them with
+    /// `AgentBuilder::foreign`.
+    pub async fn sync_foreign(
+        &mut self,
+        client: &RekuestClient,
+        agent: &str,
+        extension: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut templates = Vec::new();
+        for template in self.templates.values() {
+            let request = QueryBody {
+                variables: create_foreign_template_vars::Variables {
+                    input: create_foreign_template_vars::CreateForeignTemplateInput {
+                        agent: agent.to_string(),
+                        template: template.clone(),
+                        extension: extension.to_string(),
+                    },
+                },
+                query: create_foreign_template::QUERY,
+                operation_name: create_foreign_template::OPERATION_NAME,
+            };
+
+            let response: Response<create_foreign_template::ResponseData> =
+                client.request(&request).send().await?.json().await?;
+
+            match response.data {
+                Some(data) => {
+                    let t = data.create_foreign_template;
+                    templates.push((t.id, t.interface, t.node.id));
+                }
+                None => {
+                    return Err(
+                        format!("createForeignTemplate failed: {:?}", response.errors).into(),
+                    );
+                }
+            }
+        }
+
+        self.remember(templates);
+        Ok(())
+    }
+
+    /// Remember the server side `(template id, interface, node id)` of the
+    /// synced templates.
+    fn remember(&mut self, templates: Vec<(String, String, String)>) {
+        let mut synced = self.synced.write().unwrap();
+        synced.clear();
+        self.template_ids.clear();
+        for (template, interface, node) in templates {
+            if self.templates.contains_key(&interface) {
+                self.template_ids
+                    .insert(template.clone(), interface.clone());
+                synced.insert(interface, SyncedTemplate { template, node });
+            } else {
+                println!("Server returned unknown interface: {}", interface);
+            }
+        }
+    }
+
+    /// Look up the function for a server side template id.
+    pub fn get_function(&self, template_id: &str) -> Option<&RegisteredFunction<S>> {
+        self.functions
+            .get(self.template_ids.get(template_id)?)
+            .map(Arc::as_ref)
+    }
+
+    /// Run the function for a server side template id, answering from its
+    /// result cache where enabled.
+    pub(crate) fn start(
+        &self,
+        template_id: &str,
+        context: Context<S>,
+        args: ValueMap,
+    ) -> Option<FunctionFuture> {
+        let interface = self.template_ids.get(template_id)?;
+        let function = self.functions.get(interface)?;
+        let Some(cache) = self.caches.get(interface).cloned() else {
+            return Some(function(context, args));
+        };
+
+        // Stored returns are only handed to callers that asked for them.
+        let key = ResultCache::key(interface, &args);
+        if context.assignment().cached {
+            if let Some(returns) = cache.get(&key) {
+                return Some(Box::pin(async move { Ok(returns) }));
+            }
+        }
+
+        let store = !context.assignment().ephemeral;
+        let returns = function(context, args);
+        Some(Box::pin(async move {
+            let returns = returns.await?;
+            if store {
+                cache.insert(key, returns.clone());
+            }
+            Ok(returns)
+        }))
+    }
+
+    /// Look up the template for a server side template id.
+    pub fn get_template(&self, template_id: &str) -> Option<&create_template::TemplateInput> {
+        self.templates.get(self.template_ids.get(template_id)?)
+    }
+
+    /// Look up the function registered for `interface`.
+    pub fn function_for(&self, interface: &str) -> Option<&RegisteredFunction<S>> {
+        self.functions.get(interface).map(Arc::as_ref)
+    }
+
+    /// Look up the template registered for `interface`.
+    pub fn template_for(&self, interface: &str) -> Option<&create_template::TemplateInput> {
+        self.templates.get(interface)
+    }
+
+    /// The interfaces of all registered templates.
+    pub fn interfaces(&self) -> impl Iterator<Item = &str> {
+        self.templates.keys().map(String::as_str)
+    }
+
+    /// All registered templates, ordered by interface.
+    pub fn templates(&self) -> Vec<&create_template::TemplateInput> {
+        let mut templates: Vec<_> = self.templates.values().collect();
+        templates.sort_by(|a, b| a.interface.cmp(&b.interface));
+        templates
+    }
+}
+
+/// Deserialize `args` into `A`, reporting failures by port key.
+fn deserialize_args<A: DeserializeOwned>(
+    required: &[String],
+    args: ValueMap,
+) -> Result<A, FunctionError> {
+    for key in required {
+        if args.get(key).is_none_or(|value| value.is_null()) {
+            return Err(FunctionError::InvalidArg {
+                key: key.clone(),
+                message: "missing value".to_string(),
+            });
+        }
+    }
+
+    serde_path_to_error::deserialize(serde_json::Value::Object(args)).map_err(|e| {
+        let key = e
+            .path()
+            .iter()
+            .next()
+            .map(|segment| segment.to_string())
+            .unwrap_or_default();
+        FunctionError::InvalidArg {
+            key,
+            message: e.into_inner().to_string(),
+        }
+    })
+}
+
+/// Compare `(interface, hash)` pairs of local templates with those of the
+/// server.
+pub(crate) fn compare_hashes(
+    local: &[(String, String)],
+    server: &[(String, String)],
+) -> Vec<TemplateDrift> {
+    let mut drift = Vec::new();
+    for (interface, local) in local {
+        match server.iter().find(|(i, _)| i == interface) {
+            None => drift.push(TemplateDrift::Missing {
+                interface: interface.clone(),
+            }),
+            Some((_, server)) if server != local => drift.push(TemplateDrift::Changed {
+                interface: interface.clone(),
+                local: local.clone(),
+                server: server.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (interface, _) in server {
+        if !local.iter().any(|(i, _)| i == interface) {
+            drift.push(TemplateDrift::Stale {
+                interface: interface.clone(),
+            });
+        }
+    }
+    drift
+}
+
+/// Whether the server's copy of a template, serialized like in manifests,
+/// is up to date beyond the definition, which the hash covers. `params`
+/// and `dependencies` are what the server reports for it. The server does
+/// not report the logo, `dynamic` or viable instances, so templates setting
+/// them always differ.
+pub(crate) fn matches_server(
+    template: &serde_json::Value,
+    params: &serde_json::Value,
+    dependencies: &serde_json::Value,
+) -> bool {
+    let local_dependencies = template["dependencies"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let unreported = !template["logo"].is_null()
+        || template["dynamic"] == serde_json::Value::Bool(true)
+        || local_dependencies
+            .iter()
+            .any(|dependency| !dependency["viableInstances"].is_null());
+    if unreported {
+        return false;
+    }
+
+    // An unset params map may come back as `null` or `{}`.
+    let is_empty = |params: &serde_json::Value| match params {
+        serde_json::Value::Null => true,
+        serde_json::Value::Object(params) => params.is_empty(),
+        _ => false,
+    };
+    let params_match =
+        template["params"] == *params || is_empty(&template["params"]) && is_empty(params);
+
+    params_match && dependency_keys(&template["dependencies"]) == dependency_keys(dependencies)
+}
+
+/// The dependencies in `dependencies` as comparable strings, sorted.
+fn dependency_keys(dependencies: &serde_json::Value) -> Vec<String> {
+    let ids = |ids: &serde_json::Value| {
+        let mut ids: Vec<&str> = ids
+            .as_array()
+            .map(|ids| ids.iter().filter_map(|id| id.as_str()).collect())
+            .unwrap_or_default();
+        ids.sort();
+        ids.join(",")
+    };
+    let mut keys: Vec<String> = dependencies
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .map(|dependency| {
+            let binds = &dependency["binds"];
+            let binds = match binds.is_null() {
+                true => String::new(),
+                false => format!(
+                    "{}|{}|{}",
+                    ids(&binds["templates"]),
+                    ids(&binds["clients"]),
+                    binds["desiredInstances"]
+                ),
+            };
+            format!(
+                "{}|{}|{}|{}",
+                dependency["hash"],
+                dependency["reference"],
+                dependency["optional"].as_bool().unwrap_or(false),
+                binds
+            )
+        })
+        .collect();
+    keys.sort();
+    keys
+}
+
+/// The templates `agent` has on the server for `extension`.
+pub async fn server_templates(
+    client: &RekuestClient,
+    agent: &str,
+    extension: &str,
+) -> Result<Vec<agent_templates::AgentTemplatesAgentTemplates>, Box<dyn std::error::Error>> {
+    let request = AgentTemplates::build_query(agent_templates::Variables {
+        agent: agent.to_string(),
+        extension: extension.to_string(),
+    });
+
+    let response: Response<agent_templates::ResponseData> =
+        client.request(&request).send().await?.json().await?;
+
+    match response.data {
+        Some(data) => Ok(data.agent.templates),
+        None => Err(format!("agent templates query failed: {:?}", response.errors).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rekuest::template::Dependency;
+    use serde_json::{json, Value};
+
+    fn template() -> Template {
+        let definition = Definition::new("Add", NodeKind::FUNCTION)
+            .returns(vec![Port::new_int("sum").build()])
+            .build();
+        Template::new("add", definition)
+    }
+
+    fn matches(template: Template, params: Value, dependencies: Value) -> bool {
+        let template = serde_json::to_value(template.build()).unwrap();
+        matches_server(&template, &params, &dependencies)
+    }
+
+    #[test]
+    fn register_rejects_invalid_definitions() {
+        let definition = Definition::new("Configure", NodeKind::FUNCTION)
+            .args(vec![Port::new_model("config", "@app/config").build()])
+            .build();
+        let mut registry = FunctionRegistry::<()>::new();
+        let result = registry.register(
+            Template::new("configure", definition).build(),
+            |_, _: Value| async { Ok::<_, String>(()) },
+        );
+
+        let invalid = result.unwrap_err();
+        assert_eq!(invalid.errors.len(), 1);
+        assert!(registry.hash("configure").is_none());
+    }
+
+    async fn add(_: Context<()>, _: Value) -> Result<(), String> {
+        Ok(())
+    }
+
+    async fn subtract(_: Context<()>, _: Value) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Adds nothing
+    #[crate::test_for(add)]
+    fn adds_nothing() -> NodeTest {
+        NodeTest::new(&json!({}))
+    }
+
+    #[crate::test_for(subtract)]
+    fn subtracts_nothing() -> NodeTest {
+        NodeTest::new(&json!({}))
+    }
+
+    #[test]
+    fn tests_find_the_interface_of_their_function() {
+        let mut registry = FunctionRegistry::<()>::new();
+        registry.register(template().build(), add).unwrap();
+        assert_eq!(registry.interface_of(&add), Some("add"));
+        assert_eq!(registry.interface_of(&subtract), None);
+
+        adds_nothing::register(&mut registry).unwrap();
+        assert!(registry.hash("add.test.adds_nothing").is_some());
+        let error = subtracts_nothing::register(&mut registry).unwrap_err();
+        assert_eq!(error.to_string(), "subtract is not registered");
+    }
+
+    #[test]
+    fn unset_params_match_null_or_empty() {
+        assert!(matches(template(), json!(null), json!([])));
+        assert!(matches(template(), json!({}), json!([])));
+        assert!(!matches(template(), json!({ "a": 1 }), json!([])));
+    }
+
+    #[test]
+    fn changed_params_differ() {
+        let local = || template().params(json!({ "gpu": true }));
+        assert!(matches(local(), json!({ "gpu": true }), json!([])));
+        assert!(!matches(local(), json!({ "gpu": false }), json!([])));
+    }
+
+    #[test]
+    fn dependencies_are_compared() {
+        let local = || template().dependency(Dependency::new("upload", "abc").optional(true));
+        let dependency = |optional: bool| json!([{ "hash": "abc", "reference": "upload", "optional": optional, "binds": null }]);
+        assert!(matches(local(), json!(null), dependency(true)));
+        assert!(!matches(local(), json!(null), dependency(false)));
+        assert!(!matches(local(), json!(null), json!([])));
+    }
+
+    #[test]
+    fn unreported_fields_always_differ() {
+        assert!(!matches(
+            template().logo("logo.png"),
+            json!(null),
+            json!([])
+        ));
+        assert!(!matches(template().dynamic(true), json!(null), json!([])));
+    }
+
+    #[test]
+    fn server_templates_compare_with_the_serialized_template() {
+        let server: agent_templates::AgentTemplatesAgentTemplates = serde_json::from_value(json!({
+            "id": "1",
+            "interface": "add",
+            "params": null,
+            "node": { "id": "1", "hash": "abc" },
+            "dependencies": [{
+                "hash": "abc",
+                "reference": "upload",
+                "optional": false,
+                "binds": { "templates": ["2", "1"], "clients": [], "desiredInstances": 1 },
+            }],
+        }))
+        .unwrap();
+        let local = template().dependency(Dependency::new("upload", "abc").binds(
+            crate::rekuest::api::create_template::BindsInput {
+                templates: Some(vec!["1".to_string(), "2".to_string()]),
+                clients: Some(vec![]),
+                desired_instances: 1,
+            },
+        ));
+
+        let dependencies = serde_json::to_value(&server.dependencies).unwrap();
+        assert!(matches(local, server.params.clone(), dependencies));
     }
 }
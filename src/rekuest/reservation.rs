@@ -0,0 +1,377 @@
+use graphql_client::GraphQLQuery;
+use graphql_client::Response;
+use serde::Serialize;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use super::api::create_template;
+use super::api::reservation_events;
+use super::api::reservation_events::ReservationEventKind;
+use super::api::reserve;
+use super::api::ReservationEvents;
+use super::api::Reserve;
+use super::call::start_call;
+use super::call::to_args;
+use super::call::unreserve_reservation;
+use super::call::Call;
+use super::call::CallError;
+use super::call::Target;
+use super::client::RekuestClient;
+use super::subscription::subscribe;
+
+/// Restricts which implementations may serve a reservation or dependency.
+#[derive(Debug, Clone)]
+pub struct Binds {
+    templates: Vec<String>,
+    clients: Vec<String>,
+    desired_instances: i64,
+}
+
+impl Default for Binds {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Binds {
+    /// Bind to nothing in particular, with one desired instance.
+    pub fn new() -> Self {
+        Self {
+            templates: Vec::new(),
+            clients: Vec::new(),
+            desired_instances: 1,
+        }
+    }
+
+    /// Only accept this template.
+    pub fn template(mut self, template: &str) -> Self {
+        self.templates.push(template.to_string());
+        self
+    }
+
+    /// Only accept templates of agents run by this client.
+    pub fn client(mut self, client: &str) -> Self {
+        self.clients.push(client.to_string());
+        self
+    }
+
+    /// The number of implementations the reservation should hold on to.
+    pub fn desired_instances(mut self, desired_instances: i64) -> Self {
+        self.desired_instances = desired_instances;
+        self
+    }
+}
+
+fn non_empty(ids: Vec<String>) -> Option<Vec<String>> {
+    Some(ids).filter(|ids| !ids.is_empty())
+}
+
+impl From<Binds> for reserve::BindsInput {
+    fn from(binds: Binds) -> Self {
+        reserve::BindsInput {
+            templates: non_empty(binds.templates),
+            clients: non_empty(binds.clients),
+            desired_instances: binds.desired_instances,
+        }
+    }
+}
+
+impl From<Binds> for create_template::BindsInput {
+    fn from(binds: Binds) -> Self {
+        create_template::BindsInput {
+            templates: non_empty(binds.templates),
+            clients: non_empty(binds.clients),
+            desired_instances: binds.desired_instances,
+        }
+    }
+}
+
+/// The state of a reservation, as last reported by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReservationStatus {
+    /// Created, but not yet scheduled onto any implementation.
+    Pending,
+    /// Scheduled, but no implementation is currently connected.
+    Unconnected,
+    /// Some, but not all desired implementations are available.
+    Unhappy,
+    /// All desired implementations are available.
+    Happy,
+    /// At least one implementation is available.
+    Active,
+    /// No implementation is available.
+    Inactive,
+    /// The reservation was ended or deleted.
+    Ended,
+}
+
+impl ReservationStatus {
+    /// Whether assignations through the reservation can currently be served.
+    pub fn is_viable(&self) -> bool {
+        matches!(
+            self,
+            ReservationStatus::Active | ReservationStatus::Happy | ReservationStatus::Unhappy
+        )
+    }
+
+    /// The status a reservation event kind moves to, if it is a status.
+    fn from_kind(kind: &ReservationEventKind) -> Option<Self> {
+        match kind {
+            ReservationEventKind::PENDING => Some(ReservationStatus::Pending),
+            ReservationEventKind::UNCONNECTED => Some(ReservationStatus::Unconnected),
+            ReservationEventKind::UNHAPPY => Some(ReservationStatus::Unhappy),
+            ReservationEventKind::HAPPY => Some(ReservationStatus::Happy),
+            ReservationEventKind::ACTIVE => Some(ReservationStatus::Active),
+            ReservationEventKind::INACTIVE => Some(ReservationStatus::Inactive),
+            ReservationEventKind::ENDED | ReservationEventKind::DELETED => {
+                Some(ReservationStatus::Ended)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// What to reserve, see `RekuestClient::reserve`.
+pub struct ReservationBuilder {
+    client: RekuestClient,
+    hash: Option<String>,
+    node: Option<String>,
+    template: Option<String>,
+    title: Option<String>,
+    reference: Option<String>,
+    binds: Option<Binds>,
+    parent: Option<String>,
+}
+
+impl RekuestClient {
+    /// Start building a reservation on the node with `hash`.
+    pub fn reserve(&self, hash: &str) -> ReservationBuilder {
+        ReservationBuilder {
+            client: self.clone(),
+            hash: Some(hash.to_string()),
+            node: None,
+            template: None,
+            title: None,
+            reference: None,
+            binds: None,
+            parent: None,
+        }
+    }
+
+    /// Start building a reservation on the node with id `node`.
+    pub fn reserve_node(&self, node: &str) -> ReservationBuilder {
+        ReservationBuilder {
+            hash: None,
+            node: Some(node.to_string()),
+            ..self.reserve("")
+        }
+    }
+
+    /// Start building a reservation on one specific template.
+    pub fn reserve_template(&self, template: &str) -> ReservationBuilder {
+        ReservationBuilder {
+            hash: None,
+            template: Some(template.to_string()),
+            ..self.reserve("")
+        }
+    }
+}
+
+impl ReservationBuilder {
+    /// A human readable title for the reservation.
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    /// The reference under which the reservation is known to its waiter.
+    pub fn reference(mut self, reference: &str) -> Self {
+        self.reference = Some(reference.to_string());
+        self
+    }
+
+    /// Restrict the implementations the server may pick, e.g. to run on
+    /// specific agents.
+    pub fn binds(mut self, binds: Binds) -> Self {
+        self.binds = Some(binds);
+        self
+    }
+
+    /// Make the reservation on behalf of a running assignation.
+    pub fn parent(mut self, assignation: &str) -> Self {
+        self.parent = Some(assignation.to_string());
+        self
+    }
+
+    /// Reserve, and start following the reservation's status.
+    pub async fn create(self) -> Result<Reservation, CallError> {
+        // Subscribe first, so no status change after the reserve is missed.
+        let mut events = subscribe::<ReservationEvents>(
+            &self.client,
+            reservation_events::Variables {
+                instance_id: self.client.instance_id().to_string(),
+            },
+        )
+        .await
+        .map_err(|e| CallError::Request(e.to_string()))?;
+
+        let request = Reserve::build_query(reserve::Variables {
+            input: reserve::ReserveInput {
+                assignation_id: self.parent,
+                instance_id: self.client.instance_id().to_string(),
+                node: self.node,
+                template: self.template,
+                title: self.title,
+                hash: self.hash,
+                reference: self.reference,
+                binds: self.binds.map(Into::into),
+            },
+        });
+
+        let response: Response<reserve::ResponseData> =
+            self.client.request(&request).send().await?.json().await?;
+
+        let reservation = match response.data {
+            Some(data) => data.reserve,
+            None => {
+                return Err(CallError::Request(format!(
+                    "reserve failed: {:?}",
+                    response.errors
+                )))
+            }
+        };
+
+        let status = reserve_status(&reservation.status).unwrap_or(ReservationStatus::Pending);
+        let (status_tx, status_rx) = watch::channel(status);
+
+        let id = reservation.id.clone();
+        let watcher = tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                let event = match event {
+                    Ok(data) => data.reservation_events,
+                    Err(e) => {
+                        println!("Error receiving reservation event: {}", e);
+                        break;
+                    }
+                };
+                if event.reservation.id != id {
+                    continue;
+                }
+                if let Some(status) = ReservationStatus::from_kind(&event.reservation.status) {
+                    if status_tx.send(status).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Reservation {
+            client: self.client,
+            id: reservation.id,
+            strategy: reservation.strategy,
+            status: status_rx,
+            watcher,
+        })
+    }
+}
+
+/// The `Reserve` mutation has its own copy of the event kind enum.
+fn reserve_status(kind: &reserve::ReservationEventKind) -> Option<ReservationStatus> {
+    match kind {
+        reserve::ReservationEventKind::PENDING => Some(ReservationStatus::Pending),
+        reserve::ReservationEventKind::UNCONNECTED => Some(ReservationStatus::Unconnected),
+        reserve::ReservationEventKind::UNHAPPY => Some(ReservationStatus::Unhappy),
+        reserve::ReservationEventKind::HAPPY => Some(ReservationStatus::Happy),
+        reserve::ReservationEventKind::ACTIVE => Some(ReservationStatus::Active),
+        reserve::ReservationEventKind::INACTIVE => Some(ReservationStatus::Inactive),
+        reserve::ReservationEventKind::ENDED | reserve::ReservationEventKind::DELETED => {
+            Some(ReservationStatus::Ended)
+        }
+        _ => None,
+    }
+}
+
+/// A held reservation. Its status follows the server's `reservationEvents`;
+/// dropping it unreserves.
+pub struct Reservation {
+    client: RekuestClient,
+    id: String,
+    strategy: reserve::ReservationStrategy,
+    status: watch::Receiver<ReservationStatus>,
+    watcher: JoinHandle<()>,
+}
+
+impl Reservation {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The strategy the server uses to pick an implementation per call.
+    pub fn strategy(&self) -> &reserve::ReservationStrategy {
+        &self.strategy
+    }
+
+    /// The last known status.
+    pub fn status(&self) -> ReservationStatus {
+        *self.status.borrow()
+    }
+
+    /// A receiver that is notified on every status change.
+    pub fn watch(&self) -> watch::Receiver<ReservationStatus> {
+        self.status.clone()
+    }
+
+    /// Wait until the reservation can serve assignations. Fails if it
+    /// ends first or its status can no longer be followed.
+    pub async fn wait_until_viable(&mut self) -> Result<ReservationStatus, CallError> {
+        let status = self
+            .status
+            .wait_for(|status| status.is_viable() || *status == ReservationStatus::Ended)
+            .await
+            .map_err(|_| CallError::Disconnected)?;
+
+        match *status {
+            ReservationStatus::Ended => {
+                Err(CallError::Request(format!("reservation {} ended", self.id)))
+            }
+            status => Ok(status),
+        }
+    }
+
+    /// Assign `args` through this reservation.
+    pub async fn call<A: Serialize>(&self, args: &A) -> Result<Call, CallError> {
+        start_call(
+            &self.client,
+            Target::reservation(&self.id),
+            to_args(args)?,
+            None,
+        )
+        .await
+    }
+
+    /// Unreserve now and wait for the server to confirm.
+    pub async fn unreserve(mut self) -> anyhow::Result<()> {
+        self.watcher.abort();
+        let id = std::mem::take(&mut self.id);
+        unreserve_reservation(&self.client, &id).await
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.watcher.abort();
+        if self.id.is_empty() {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let client = self.client.clone();
+        let id = self.id.clone();
+        runtime.spawn(async move {
+            if let Err(e) = unreserve_reservation(&client, &id).await {
+                println!("Failed to unreserve {}: {}", id, e);
+            }
+        });
+    }
+}
@@ -0,0 +1,263 @@
+use std::marker::PhantomData;
+
+use futures::{Stream, StreamExt};
+use graphql_client::GraphQLQuery;
+use graphql_client::QueryBody;
+use graphql_client::Response;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::api::archive_state;
+use super::api::create_state_schema;
+use super::api::create_state_schema_vars;
+use super::api::create_template::PortInput;
+use super::api::get_state;
+use super::api::set_state;
+use super::api::state_update_events;
+use super::api::update_state;
+use super::api::ArchiveState;
+use super::api::GetState;
+use super::api::SetState;
+use super::api::StateUpdateEvents;
+use super::api::UpdateState;
+use super::client::RekuestClient;
+use super::registry::ValueMap;
+use super::subscription::subscribe;
+
+pub use arkirust_macros::StateSchema;
+
+/// A struct that can be published as rekuest state.
+///
+/// Usually derived: `#[derive(Serialize, Deserialize, StateSchema)]` maps
+/// every field to a port of the matching kind.
+pub trait StateSchema: Serialize + DeserializeOwned {
+    /// The name the schema is registered under.
+    const NAME: &'static str;
+
+    /// One port per field, keyed by field name.
+    fn ports() -> Vec<PortInput>;
+}
+
+/// Publishes the state `S` of this agent.
+pub struct StatePublisher<S: StateSchema> {
+    client: RekuestClient,
+    schema: String,
+    /// The id of the published state, known after the first `set`.
+    id: String,
+    last: Value,
+    state: PhantomData<S>,
+}
+
+impl<S: StateSchema> StatePublisher<S> {
+    /// Register the schema of `S` and publish `initial` as its first value.
+    pub async fn create(client: &RekuestClient, initial: &S) -> anyhow::Result<Self> {
+        let request = QueryBody {
+            variables: create_state_schema_vars::Variables {
+                input: create_state_schema_vars::CreateStateSchemaInput {
+                    state_schema: create_state_schema_vars::StateSchemaInput {
+                        ports: S::ports(),
+                        name: S::NAME.to_string(),
+                    },
+                },
+            },
+            query: create_state_schema::QUERY,
+            operation_name: create_state_schema::OPERATION_NAME,
+        };
+
+        let response: Response<create_state_schema::ResponseData> =
+            client.request(&request).send().await?.json().await?;
+
+        let schema = match response.data {
+            Some(data) => data.create_state_schema.id,
+            None => anyhow::bail!("createStateSchema failed: {:?}", response.errors),
+        };
+
+        let mut publisher = Self {
+            client: client.clone(),
+            schema,
+            id: String::new(),
+            last: Value::Null,
+            state: PhantomData,
+        };
+        publisher.set(initial).await?;
+        Ok(publisher)
+    }
+
+    /// The id of the registered state schema.
+    pub fn schema(&self) -> &str {
+        &self.schema
+    }
+
+    /// The id of the published state, e.g. to show it in a `Panel`.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Publish `state` as a whole with `setState`.
+    pub async fn set(&mut self, state: &S) -> anyhow::Result<()> {
+        let value = to_map(state)?;
+
+        let request = SetState::build_query(set_state::Variables {
+            input: set_state::SetStateInput {
+                state_schema: self.schema.clone(),
+                instance_id: self.client.instance_id().to_string(),
+                value: value.clone(),
+            },
+        });
+
+        let response: Response<set_state::ResponseData> =
+            self.client.request(&request).send().await?.json().await?;
+
+        match response.data {
+            Some(data) => {
+                self.id = data.set_state.id;
+                self.last = Value::Object(value);
+                Ok(())
+            }
+            None => anyhow::bail!("setState failed: {:?}", response.errors),
+        }
+    }
+
+    /// Publish only what changed since the last published value, as JSON
+    /// patch operations through `updateState`. Nothing is sent if nothing
+    /// changed.
+    pub async fn update(&mut self, state: &S) -> anyhow::Result<()> {
+        let value = Value::Object(to_map(state)?);
+
+        let mut patches = Vec::new();
+        diff("", &self.last, &value, &mut patches);
+        if patches.is_empty() {
+            return Ok(());
+        }
+
+        let request = UpdateState::build_query(update_state::Variables {
+            input: update_state::UpdateStateInput {
+                state_schema: self.schema.clone(),
+                instance_id: self.client.instance_id().to_string(),
+                patches,
+            },
+        });
+
+        let response: Response<update_state::ResponseData> =
+            self.client.request(&request).send().await?.json().await?;
+
+        match response.data {
+            Some(_) => {
+                self.last = value;
+                Ok(())
+            }
+            None => anyhow::bail!("updateState failed: {:?}", response.errors),
+        }
+    }
+
+    /// Archive the current state, e.g. when shutting down.
+    pub async fn archive(self) -> anyhow::Result<()> {
+        let request = ArchiveState::build_query(archive_state::Variables {
+            input: archive_state::ArchiveStateInput {
+                state_schema: self.schema.clone(),
+            },
+        });
+
+        let response: Response<archive_state::ResponseData> =
+            self.client.request(&request).send().await?.json().await?;
+
+        match response.data {
+            Some(_) => Ok(()),
+            None => anyhow::bail!("archiveState failed: {:?}", response.errors),
+        }
+    }
+}
+
+/// Follow the state with id `state`, e.g. of another agent. Yields the
+/// current value first, then every update.
+pub async fn watch_state<S: StateSchema>(
+    client: &RekuestClient,
+    state: &str,
+) -> anyhow::Result<impl Stream<Item = anyhow::Result<S>>> {
+    // Subscribe first, so no update between the query and the subscription
+    // is missed.
+    let events = subscribe::<StateUpdateEvents>(
+        client,
+        state_update_events::Variables {
+            state_id: state.to_string(),
+        },
+    )
+    .await?;
+
+    let request = GetState::build_query(get_state::Variables {
+        id: state.to_string(),
+    });
+
+    let response: Response<get_state::ResponseData> =
+        client.request(&request).send().await?.json().await?;
+
+    let current = match response.data {
+        Some(data) => data.state.value,
+        None => anyhow::bail!("state query failed: {:?}", response.errors),
+    };
+
+    let current = futures::stream::once(async move { from_map(current) });
+    let updates = futures::stream::unfold(events, |mut events| async move {
+        let update = match events.next().await? {
+            Ok(data) => from_map(data.state_update_events.value),
+            Err(e) => Err(e),
+        };
+        Some((update, events))
+    });
+
+    Ok(current.chain(updates))
+}
+
+fn to_map<S: Serialize>(state: &S) -> anyhow::Result<ValueMap> {
+    match serde_json::to_value(state)? {
+        Value::Object(map) => Ok(map),
+        other => anyhow::bail!("state must serialize to a map of port keys, got {}", other),
+    }
+}
+
+fn from_map<S: DeserializeOwned>(value: ValueMap) -> anyhow::Result<S> {
+    Ok(serde_json::from_value(Value::Object(value))?)
+}
+
+/// Append the JSON patch operations turning `old` into `new` at `path`.
+/// Maps are diffed key by key, anything else is replaced as a whole.
+fn diff(path: &str, old: &Value, new: &Value, patches: &mut Vec<ValueMap>) {
+    if old == new {
+        return;
+    }
+
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old_value) in old {
+                let path = format!("{}/{}", path, escape(key));
+                match new.get(key) {
+                    Some(new_value) => diff(&path, old_value, new_value, patches),
+                    None => patches.push(patch("remove", &path, None)),
+                }
+            }
+            for (key, new_value) in new {
+                if !old.contains_key(key) {
+                    let path = format!("{}/{}", path, escape(key));
+                    patches.push(patch("add", &path, Some(new_value.clone())));
+                }
+            }
+        }
+        _ => patches.push(patch("replace", path, Some(new.clone()))),
+    }
+}
+
+fn patch(op: &str, path: &str, value: Option<Value>) -> ValueMap {
+    let mut patch = ValueMap::new();
+    patch.insert("op".to_string(), Value::String(op.to_string()));
+    patch.insert("path".to_string(), Value::String(path.to_string()));
+    if let Some(value) = value {
+        patch.insert("value".to_string(), value);
+    }
+    patch
+}
+
+/// Escape a key as a JSON pointer token.
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
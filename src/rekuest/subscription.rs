@@ -0,0 +1,134 @@
+use std::marker::PhantomData;
+
+use futures::{SinkExt, StreamExt};
+use graphql_client::{GraphQLQuery, Response};
+use serde_json::json;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use super::client::RekuestClient;
+
+/// The only operation id used; every subscription gets its own connection.
+const OPERATION_ID: &str = "1";
+
+/// A running GraphQL subscription, speaking the `graphql-transport-ws`
+/// protocol. Dropping it closes the connection.
+pub struct Subscription<Q: GraphQLQuery> {
+    ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    query: PhantomData<Q>,
+}
+
+/// Open a websocket to rekuest and start the subscription `Q`.
+///
+/// Returns once the server acknowledged the connection and the subscribe
+/// message is sent, so no event that happens afterwards is missed.
+pub async fn subscribe<Q: GraphQLQuery>(
+    client: &RekuestClient,
+    variables: Q::Variables,
+) -> anyhow::Result<Subscription<Q>> {
+    let mut request = client.ws_endpoint_url().into_client_request()?;
+    request.headers_mut().insert(
+        "Sec-WebSocket-Protocol",
+        HeaderValue::from_static("graphql-transport-ws"),
+    );
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(request).await?;
+
+    let init = json!({
+        "type": "connection_init",
+        "payload": { "token": client.token() },
+    });
+    ws.send(Message::Text(init.to_string())).await?;
+
+    loop {
+        let msg = match ws.next().await {
+            Some(msg) => msg?,
+            None => anyhow::bail!("connection closed before it was acknowledged"),
+        };
+        let msg: serde_json::Value = match msg {
+            Message::Text(text) => serde_json::from_str(&text)?,
+            _ => continue,
+        };
+        match msg["type"].as_str() {
+            Some("connection_ack") => break,
+            Some("ping") => {
+                ws.send(Message::Text(json!({ "type": "pong" }).to_string()))
+                    .await?
+            }
+            _ => anyhow::bail!("unexpected message during connection_init: {}", msg),
+        }
+    }
+
+    let subscribe = json!({
+        "id": OPERATION_ID,
+        "type": "subscribe",
+        "payload": Q::build_query(variables),
+    });
+    ws.send(Message::Text(subscribe.to_string())).await?;
+
+    Ok(Subscription {
+        ws,
+        query: PhantomData,
+    })
+}
+
+impl<Q: GraphQLQuery> Subscription<Q> {
+    /// Wait for the next event. Returns `None` once the server completed
+    /// the subscription or closed the connection.
+    pub async fn next(&mut self) -> Option<anyhow::Result<Q::ResponseData>> {
+        loop {
+            let msg = match self.ws.next().await? {
+                Ok(Message::Text(text)) => text,
+                Ok(Message::Close(_)) => return None,
+                Ok(_) => continue,
+                Err(e) => return Some(Err(e.into())),
+            };
+            let msg: serde_json::Value = match serde_json::from_str(&msg) {
+                Ok(msg) => msg,
+                Err(e) => return Some(Err(e.into())),
+            };
+
+            match msg["type"].as_str() {
+                Some("next") => {
+                    let response: Response<Q::ResponseData> =
+                        match serde_json::from_value(msg["payload"].clone()) {
+                            Ok(response) => response,
+                            Err(e) => return Some(Err(e.into())),
+                        };
+                    return Some(match response.data {
+                        Some(data) => Ok(data),
+                        None => Err(anyhow::anyhow!(
+                            "subscription failed: {:?}",
+                            response.errors
+                        )),
+                    });
+                }
+                Some("ping") => {
+                    let pong = Message::Text(json!({ "type": "pong" }).to_string());
+                    if let Err(e) = self.ws.send(pong).await {
+                        return Some(Err(e.into()));
+                    }
+                }
+                Some("error") => {
+                    return Some(Err(anyhow::anyhow!(
+                        "subscription failed: {}",
+                        msg["payload"]
+                    )))
+                }
+                Some("complete") => return None,
+                _ => continue,
+            }
+        }
+    }
+
+    /// Stop the subscription and close the connection.
+    pub async fn close(mut self) -> anyhow::Result<()> {
+        let complete = json!({ "id": OPERATION_ID, "type": "complete" });
+        self.ws.send(Message::Text(complete.to_string())).await?;
+        self.ws.close(None).await?;
+        Ok(())
+    }
+}
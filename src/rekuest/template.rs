@@ -0,0 +1,120 @@
+use super::api::create_template::{BindsInput, DefinitionInput, DependencyInput, TemplateInput};
+use super::hash::hash_definition;
+
+/// Builder for a `TemplateInput`, the unit a function is registered with.
+pub struct Template {
+    interface: String,
+    definition: DefinitionInput,
+    dependencies: Vec<DependencyInput>,
+    logo: Option<String>,
+    params: Option<serde_json::Value>,
+    dynamic: bool,
+}
+
+impl Template {
+    /// Create a new `Template` for `definition`, served under `interface`.
+    pub fn new(interface: &str, definition: DefinitionInput) -> Self {
+        Self {
+            interface: interface.to_string(),
+            definition,
+            dependencies: Vec::new(),
+            logo: None,
+            params: None,
+            dynamic: false,
+        }
+    }
+
+    /// Declare that the function calls another node, see `Dependency`.
+    pub fn dependency(mut self, dependency: Dependency) -> Self {
+        self.dependencies.push(dependency.build());
+        self
+    }
+
+    /// Set the logo shown for this template.
+    pub fn logo(mut self, logo: &str) -> Self {
+        self.logo = Some(logo.to_string());
+        self
+    }
+
+    /// Set the params of this template.
+    pub fn params(mut self, params: serde_json::Value) -> Self {
+        self.params = Some(params);
+        self
+    }
+
+    /// Specify whether this template is dynamic.
+    pub fn dynamic(mut self, dynamic: bool) -> Self {
+        self.dynamic = dynamic;
+        self
+    }
+
+    pub fn build(self) -> TemplateInput {
+        TemplateInput {
+            definition: self.definition,
+            interface: self.interface,
+            dependencies: self.dependencies,
+            logo: self.logo,
+            params: self.params,
+            dynamic: self.dynamic,
+        }
+    }
+}
+
+/// Builder for a `DependencyInput`: "this function calls node X".
+///
+/// The server creates a reservation for every dependency of a provision.
+/// Inside the function the reservation is reachable through
+/// `Context::dependency(reference)`.
+pub struct Dependency {
+    reference: String,
+    hash: String,
+    binds: Option<BindsInput>,
+    optional: bool,
+    viable_instances: Option<i64>,
+}
+
+impl Dependency {
+    /// Depend on the node with `hash`, referred to as `reference`.
+    pub fn new(reference: &str, hash: &str) -> Self {
+        Self {
+            reference: reference.to_string(),
+            hash: hash.to_string(),
+            binds: None,
+            optional: false,
+            viable_instances: None,
+        }
+    }
+
+    /// Depend on the node `definition` describes, referred to as `reference`.
+    pub fn on(reference: &str, definition: &DefinitionInput) -> Self {
+        Self::new(reference, &hash_definition(definition))
+    }
+
+    /// Only accept the given templates or clients as implementations.
+    pub fn binds(mut self, binds: impl Into<BindsInput>) -> Self {
+        self.binds = Some(binds.into());
+        self
+    }
+
+    /// Specify whether the function can run without this dependency.
+    pub fn optional(mut self, optional: bool) -> Self {
+        self.optional = optional;
+        self
+    }
+
+    /// The number of implementations that need to be available.
+    pub fn viable_instances(mut self, viable_instances: i64) -> Self {
+        self.viable_instances = Some(viable_instances);
+        self
+    }
+
+    pub fn build(self) -> DependencyInput {
+        DependencyInput {
+            hash: Some(self.hash),
+            reference: Some(self.reference),
+            binds: self.binds,
+            optional: self.optional,
+            viable_instances: self.viable_instances,
+        }
+    }
+}
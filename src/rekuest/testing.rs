@@ -0,0 +1,174 @@
+use graphql_client::GraphQLQuery;
+use graphql_client::Response;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::api::create_test_case;
+use super::api::create_test_result;
+use super::api::CreateTestCase;
+use super::api::CreateTestResult;
+use super::call::to_args;
+use super::client::RekuestClient;
+use super::context::Context;
+use super::registry::RegisteredFunction;
+use super::registry::ValueMap;
+
+pub use arkirust_macros::test_for;
+
+/// Checks the returns of a tested node.
+pub type TestCheck = Box<dyn Fn(ValueMap) -> Result<(), String> + Send + Sync>;
+
+/// A test of a registered node: sample args, and optionally a check on
+/// what the node returns for them. Without a check, a node passes if it
+/// returns without error.
+pub struct NodeTest {
+    args: Result<ValueMap, String>,
+    check: Option<TestCheck>,
+}
+
+impl NodeTest {
+    /// Call the node with `args`.
+    pub fn new<A: Serialize>(args: &A) -> Self {
+        Self {
+            args: to_args(args).map_err(|e| e.to_string()),
+            check: None,
+        }
+    }
+
+    /// Check the returns, deserialized into `R`. An `Err` fails the test
+    /// with its message.
+    pub fn check<R, F>(mut self, check: F) -> Self
+    where
+        R: DeserializeOwned,
+        F: Fn(R) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.check = Some(Box::new(move |returns| {
+            let returns = serde_json::from_value(serde_json::Value::Object(returns))
+                .map_err(|e| format!("Invalid returns: {}", e))?;
+            check(returns)
+        }));
+        self
+    }
+
+    /// Run the test against `function`.
+    pub async fn run<S>(
+        &self,
+        function: &RegisteredFunction<S>,
+        context: Context<S>,
+    ) -> TestResult {
+        let args = match &self.args {
+            Ok(args) => args.clone(),
+            Err(e) => return TestResult::failed(format!("Invalid args: {}", e)),
+        };
+
+        let returns = match function(context, args).await {
+            Ok(returns) => returns,
+            Err(e) => return TestResult::failed(e.to_string()),
+        };
+
+        match &self.check {
+            Some(check) => match check(returns) {
+                Ok(()) => TestResult::passed(),
+                Err(e) => TestResult::failed(e),
+            },
+            None => TestResult::passed(),
+        }
+    }
+}
+
+/// Whether a test passed, and why not.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestResult {
+    pub passed: bool,
+    pub message: Option<String>,
+}
+
+impl TestResult {
+    fn passed() -> Self {
+        Self {
+            passed: true,
+            message: None,
+        }
+    }
+
+    fn failed(message: String) -> Self {
+        Self {
+            passed: false,
+            message: Some(message),
+        }
+    }
+
+    /// The returns of a tester template, see `FunctionRegistry::add_test`.
+    pub(crate) fn into_returns(self) -> ValueMap {
+        let mut returns = ValueMap::new();
+        returns.insert("passed".to_string(), self.passed.into());
+        returns.insert(
+            "result".to_string(),
+            self.message.unwrap_or_default().into(),
+        );
+        returns
+    }
+}
+
+/// The result of a test run, see `FunctionRegistry::run_tests`.
+#[derive(Debug, Clone)]
+pub struct TestOutcome {
+    /// The interface of the tested template.
+    pub interface: String,
+    /// The name of the test.
+    pub name: String,
+    pub result: TestResult,
+}
+
+/// A template and node id the server assigned to a registered template.
+#[derive(Debug, Clone)]
+pub struct SyncedTemplate {
+    pub template: String,
+    pub node: String,
+}
+
+/// Record `result` as a result of the test case `name`, run by `tester`
+/// against `tested`.
+pub async fn report_test_result(
+    client: &RekuestClient,
+    tested: &SyncedTemplate,
+    tester: &SyncedTemplate,
+    name: &str,
+    description: &str,
+    result: &TestResult,
+) -> anyhow::Result<String> {
+    let request = CreateTestCase::build_query(create_test_case::Variables {
+        input: create_test_case::CreateTestCaseInput {
+            node: tested.node.clone(),
+            tester: tester.node.clone(),
+            description: Some(description.to_string()).filter(|d| !d.is_empty()),
+            name: Some(name.to_string()),
+        },
+    });
+
+    let response: Response<create_test_case::ResponseData> =
+        client.request(&request).send().await?.json().await?;
+
+    let case = match response.data {
+        Some(data) => data.create_test_case.id,
+        None => anyhow::bail!("createTestCase failed: {:?}", response.errors),
+    };
+
+    let request = CreateTestResult::build_query(create_test_result::Variables {
+        input: create_test_result::CreateTestResultInput {
+            case,
+            tester: tester.template.clone(),
+            template: tested.template.clone(),
+            passed: result.passed,
+            result: result.message.clone(),
+        },
+    });
+
+    let response: Response<create_test_result::ResponseData> =
+        client.request(&request).send().await?.json().await?;
+
+    match response.data {
+        Some(data) => Ok(data.create_test_result.id),
+        None => anyhow::bail!("createTestResult failed: {:?}", response.errors),
+    }
+}
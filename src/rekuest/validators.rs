@@ -0,0 +1,294 @@
+use super::api::create_template::ValidatorInput;
+
+/// What a `Validator` checks, rendered into a `ValidatorFunction` on build.
+enum Check {
+    /// A raw javascript `(value, otherValues) => string | undefined` function.
+    Raw(String),
+    /// A javascript boolean expression over `v` (the value) and `o` (the
+    /// other values), the value is valid if it evaluates to true.
+    Expression {
+        expression: String,
+        allow_null: bool,
+    },
+}
+
+/// Builder for a `ValidatorInput`.
+///
+/// Validators run client side before an assignation is sent, the server
+/// receives them as a `ValidatorFunction` with the signature
+/// `(value, otherValues) => string | undefined`. Values of other ports are only
+/// passed in if they are listed as dependencies, which the constructors here
+/// take care of.
+pub struct Validator {
+    check: Check,
+    dependencies: Vec<String>,
+    label: Option<String>,
+    error_message: Option<String>,
+}
+
+impl Validator {
+    /// Null values pass unless `allow_null` is unset, nullability is the
+    /// port's concern.
+    fn expression(expression: String, allow_null: bool, error_message: String) -> Self {
+        Self {
+            check: Check::Expression {
+                expression,
+                allow_null,
+            },
+            dependencies: Vec::new(),
+            label: None,
+            error_message: Some(error_message),
+        }
+    }
+
+    fn compare(operator: &str, other: &str, error_message: String) -> Self {
+        let mut validator = Self::expression(
+            format!("v {} o[{}]", operator, js_string(other)),
+            true,
+            error_message,
+        );
+        validator.dependencies.push(other.to_string());
+        validator
+    }
+
+    /// A validator from a hand written javascript function.
+    pub fn new(function: &str) -> Self {
+        Self {
+            check: Check::Raw(function.to_string()),
+            dependencies: Vec::new(),
+            label: None,
+            error_message: None,
+        }
+    }
+
+    /// The value must lie within `min..=max`. Infinite bounds are written as
+    /// `Infinity`.
+    ///
+    /// # Panics
+    ///
+    /// If a bound is NaN.
+    pub fn range(min: impl Into<f64>, max: impl Into<f64>) -> Self {
+        let (min, max) = (js_number(min.into()), js_number(max.into()));
+        Self::expression(
+            format!("v >= {} && v <= {}", min, max),
+            true,
+            format!("Must be between {} and {}", min, max),
+        )
+    }
+
+    /// The value must be at least `min`.
+    ///
+    /// # Panics
+    ///
+    /// If `min` is NaN.
+    pub fn min(min: impl Into<f64>) -> Self {
+        let min = js_number(min.into());
+        Self::expression(
+            format!("v >= {}", min),
+            true,
+            format!("Must be at least {}", min),
+        )
+    }
+
+    /// The value must be at most `max`.
+    ///
+    /// # Panics
+    ///
+    /// If `max` is NaN.
+    pub fn max(max: impl Into<f64>) -> Self {
+        let max = js_number(max.into());
+        Self::expression(
+            format!("v <= {}", max),
+            true,
+            format!("Must be at most {}", max),
+        )
+    }
+
+    /// The (string) value must match the regular expression `pattern`.
+    pub fn regex(pattern: &str) -> Self {
+        Self::expression(
+            format!("new RegExp({}).test(v)", js_string(pattern)),
+            true,
+            format!("Must match {}", pattern),
+        )
+    }
+
+    /// The value must be set and, for strings and lists, not be empty.
+    pub fn non_empty() -> Self {
+        Self::expression(
+            "v !== null && v !== undefined && (v.length === undefined || v.length > 0)".to_string(),
+            false,
+            "Must not be empty".to_string(),
+        )
+    }
+
+    /// The value must be greater than the value of port `other`.
+    pub fn greater_than(other: &str) -> Self {
+        Self::compare(">", other, format!("Must be greater than {}", other))
+    }
+
+    /// The value must be greater than or equal to the value of port `other`.
+    pub fn greater_or_equal(other: &str) -> Self {
+        Self::compare(">=", other, format!("Must not be less than {}", other))
+    }
+
+    /// The value must be less than the value of port `other`.
+    pub fn less_than(other: &str) -> Self {
+        Self::compare("<", other, format!("Must be less than {}", other))
+    }
+
+    /// The value must be less than or equal to the value of port `other`.
+    pub fn less_or_equal(other: &str) -> Self {
+        Self::compare("<=", other, format!("Must not be greater than {}", other))
+    }
+
+    /// The value must differ from the value of port `other`.
+    pub fn not_equal(other: &str) -> Self {
+        Self::compare("!==", other, format!("Must differ from {}", other))
+    }
+
+    /// Declare another port whose value the function reads from `otherValues`.
+    pub fn dependency(mut self, key: &str) -> Self {
+        self.dependencies.push(key.to_string());
+        self
+    }
+
+    pub fn label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    /// Replace the message shown when the value is invalid.
+    pub fn error_message(mut self, error_message: &str) -> Self {
+        self.error_message = Some(error_message.to_string());
+        self
+    }
+
+    pub fn build(self) -> ValidatorInput {
+        let function = match self.check {
+            Check::Raw(function) => function,
+            Check::Expression {
+                expression,
+                allow_null,
+            } => {
+                let null_check = if allow_null {
+                    "v === null || v === undefined || "
+                } else {
+                    ""
+                };
+                format!(
+                    "(v, o) => ({}({})) ? undefined : {}",
+                    null_check,
+                    expression,
+                    js_string(self.error_message.as_deref().unwrap_or("Invalid value"))
+                )
+            }
+        };
+
+        ValidatorInput {
+            function,
+            dependencies: Some(self.dependencies),
+            label: self.label,
+            error_message: self.error_message,
+        }
+    }
+}
+
+impl From<Validator> for ValidatorInput {
+    fn from(validator: Validator) -> Self {
+        validator.build()
+    }
+}
+
+/// Write `value` as a javascript number literal. Rust formats infinity as
+/// `inf`, which javascript does not know, and no comparison with NaN is ever
+/// true, so a NaN bound is a bug in the caller.
+fn js_number(value: f64) -> String {
+    assert!(!value.is_nan(), "validator bounds must not be NaN");
+    if value == f64::INFINITY {
+        "Infinity".to_string()
+    } else if value == f64::NEG_INFINITY {
+        "-Infinity".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Quote `value` as a javascript string literal.
+fn js_string(value: &str) -> String {
+    // JSON strings are valid javascript string literals
+    serde_json::Value::from(value).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(validator: Validator) -> String {
+        validator.build().function
+    }
+
+    #[test]
+    fn bound_expressions() {
+        assert_eq!(
+            function(Validator::range(1, 2.5)),
+            "(v, o) => (v === null || v === undefined || (v >= 1 && v <= 2.5)) \
+             ? undefined : \"Must be between 1 and 2.5\""
+        );
+        assert_eq!(
+            function(Validator::min(-0.5)),
+            "(v, o) => (v === null || v === undefined || (v >= -0.5)) \
+             ? undefined : \"Must be at least -0.5\""
+        );
+        assert_eq!(
+            function(Validator::max(10)),
+            "(v, o) => (v === null || v === undefined || (v <= 10)) \
+             ? undefined : \"Must be at most 10\""
+        );
+    }
+
+    #[test]
+    fn infinite_bounds() {
+        assert_eq!(
+            function(Validator::range(f64::NEG_INFINITY, f64::INFINITY)),
+            "(v, o) => (v === null || v === undefined || (v >= -Infinity && v <= Infinity)) \
+             ? undefined : \"Must be between -Infinity and Infinity\""
+        );
+        assert_eq!(
+            function(Validator::min(f64::INFINITY)),
+            "(v, o) => (v === null || v === undefined || (v >= Infinity)) \
+             ? undefined : \"Must be at least Infinity\""
+        );
+        assert_eq!(
+            function(Validator::max(f64::NEG_INFINITY)),
+            "(v, o) => (v === null || v === undefined || (v <= -Infinity)) \
+             ? undefined : \"Must be at most -Infinity\""
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "NaN")]
+    fn nan_bounds_are_rejected() {
+        Validator::range(0, f64::NAN);
+    }
+
+    #[test]
+    fn comparisons_depend_on_the_other_port() {
+        let validator = Validator::greater_than("min").build();
+        assert_eq!(
+            validator.function,
+            "(v, o) => (v === null || v === undefined || (v > o[\"min\"])) \
+             ? undefined : \"Must be greater than min\""
+        );
+        assert_eq!(validator.dependencies, Some(vec!["min".to_string()]));
+    }
+
+    #[test]
+    fn non_empty_rejects_null() {
+        assert_eq!(
+            function(Validator::non_empty()),
+            "(v, o) => ((v !== null && v !== undefined && (v.length === undefined || v.length > 0))) \
+             ? undefined : \"Must not be empty\""
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use super::api::create_template::{
+    AssignWidgetInput, AssignWidgetKind, ChoiceInput, ReturnWidgetInput, ReturnWidgetKind,
+};
+use super::ports::IntoChildPort;
+
+/// A single option of a CHOICE widget.
+pub struct Choice {
+    value: serde_json::Value,
+    label: String,
+    description: Option<String>,
+}
+
+impl Choice {
+    pub fn new(value: impl Into<serde_json::Value>, label: &str) -> Self {
+        Self {
+            value: value.into(),
+            label: label.to_string(),
+            description: None,
+        }
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+}
+
+impl From<Choice> for ChoiceInput {
+    fn from(choice: Choice) -> Self {
+        ChoiceInput {
+            value: choice.value,
+            label: choice.label,
+            description: choice.description,
+        }
+    }
+}
+
+/// Constructors for `AssignWidgetInput`, one per `AssignWidgetKind`.
+/// Each constructor requires exactly the fields its kind needs.
+pub struct Widget {}
+
+impl Widget {
+    fn empty(kind: AssignWidgetKind) -> AssignWidgetInput {
+        AssignWidgetInput {
+            as_paragraph: None,
+            kind,
+            query: None,
+            choices: None,
+            state_choices: None,
+            follow_value: None,
+            min: None,
+            max: None,
+            step: None,
+            placeholder: None,
+            hook: None,
+            ward: None,
+            fallback: Box::new(None),
+            filters: None,
+        }
+    }
+
+    /// A search field that runs `query` against the service registered as `ward`.
+    pub fn search(query: &str, ward: &str) -> AssignWidgetInput {
+        AssignWidgetInput {
+            query: Some(query.to_string()),
+            ward: Some(ward.to_string()),
+            ..Self::empty(AssignWidgetKind::SEARCH)
+        }
+    }
+
+    /// Like `search`, with extra filter ports the user can set to narrow the query.
+    pub fn search_filtered<C: IntoChildPort>(
+        query: &str,
+        ward: &str,
+        filters: Vec<C>,
+    ) -> AssignWidgetInput {
+        AssignWidgetInput {
+            filters: Some(filters.into_iter().map(|f| f.build_child()).collect()),
+            ..Self::search(query, ward)
+        }
+    }
+
+    /// A dropdown offering a fixed set of `choices`.
+    pub fn choices(choices: impl IntoIterator<Item = Choice>) -> AssignWidgetInput {
+        AssignWidgetInput {
+            choices: Some(choices.into_iter().map(ChoiceInput::from).collect()),
+            ..Self::empty(AssignWidgetKind::CHOICE)
+        }
+    }
+
+    /// A slider between `min` and `max`.
+    pub fn slider(min: f64, max: f64, step: f64) -> AssignWidgetInput {
+        AssignWidgetInput {
+            min: Some(min),
+            max: Some(max),
+            step: Some(step),
+            ..Self::empty(AssignWidgetKind::SLIDER)
+        }
+    }
+
+    /// A text input, rendered as a multi-line field if `as_paragraph` is set.
+    pub fn string(placeholder: &str, as_paragraph: bool) -> AssignWidgetInput {
+        AssignWidgetInput {
+            placeholder: Some(placeholder.to_string()),
+            as_paragraph: Some(as_paragraph),
+            ..Self::empty(AssignWidgetKind::STRING)
+        }
+    }
+
+    /// A dropdown whose choices are read from the state at `state_choices`.
+    pub fn state_choices(state_choices: &str) -> AssignWidgetInput {
+        AssignWidgetInput {
+            state_choices: Some(state_choices.to_string()),
+            ..Self::empty(AssignWidgetKind::STATE_CHOICE)
+        }
+    }
+
+    /// A widget implemented by the `hook` of the app registered as `ward`.
+    /// Clients that do not know the hook render `fallback` instead.
+    pub fn custom(
+        hook: &str,
+        ward: &str,
+        fallback: Option<AssignWidgetInput>,
+    ) -> AssignWidgetInput {
+        AssignWidgetInput {
+            hook: Some(hook.to_string()),
+            ward: Some(ward.to_string()),
+            fallback: Box::new(fallback),
+            ..Self::empty(AssignWidgetKind::CUSTOM)
+        }
+    }
+}
+
+/// Constructors for `ReturnWidgetInput`, one per `ReturnWidgetKind`.
+pub struct ReturnWidget {}
+
+impl ReturnWidget {
+    fn empty(kind: ReturnWidgetKind) -> ReturnWidgetInput {
+        ReturnWidgetInput {
+            kind,
+            query: None,
+            choices: None,
+            min: None,
+            max: None,
+            step: None,
+            placeholder: None,
+            hook: None,
+            ward: None,
+        }
+    }
+
+    /// Displays the returned value as the label of the matching choice.
+    pub fn choices(choices: impl IntoIterator<Item = Choice>) -> ReturnWidgetInput {
+        ReturnWidgetInput {
+            choices: Some(choices.into_iter().map(ChoiceInput::from).collect()),
+            ..Self::empty(ReturnWidgetKind::CHOICE)
+        }
+    }
+
+    /// A widget implemented by the `hook` of the app registered as `ward`.
+    pub fn custom(hook: &str, ward: &str) -> ReturnWidgetInput {
+        ReturnWidgetInput {
+            hook: Some(hook.to_string()),
+            ward: Some(ward.to_string()),
+            ..Self::empty(ReturnWidgetKind::CUSTOM)
+        }
+    }
+}
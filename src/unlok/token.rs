@@ -1,6 +1,5 @@
 use super::fakt::UnlokFakt;
 use oauth2::basic::BasicClient;
-use oauth2::http::{request, response};
 use oauth2::reqwest::async_http_client; // Use the provided async HTTP client function
 use oauth2::{AuthUrl, ClientId, ClientSecret, Scope, TokenResponse, TokenUrl};
 
@@ -14,7 +13,7 @@ pub async fn get_auth_token(config: UnlokFakt) -> Result<String, Box<dyn std::er
 
     let token_result = client
         .exchange_client_credentials()
-        .add_scopes(config.scopes.into_iter().map(|s| Scope::new(s)))
+        .add_scopes(config.scopes.into_iter().map(Scope::new))
         // Use the async_http_client function provided by the oauth2 crate
         .request_async(async_http_client)
         .await?;
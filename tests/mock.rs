@@ -0,0 +1,330 @@
+#![cfg(feature = "mock")]
+
+use arkirust::rekuest::agent::{create_agent, AgentBuilder};
+use arkirust::rekuest::agent_protocol::{AgentMessage, Assignment};
+use arkirust::rekuest::api::create_template::NodeKind;
+use arkirust::rekuest::call::Target;
+use arkirust::rekuest::client::{RekuestClient, DEFAULT_INSTANCE_ID};
+use arkirust::rekuest::context::Context;
+use arkirust::rekuest::definition::Definition;
+use arkirust::rekuest::mock::MockRekuest;
+use arkirust::rekuest::ports::Port;
+use arkirust::rekuest::registry::{FunctionRegistry, ValueMap};
+use arkirust::rekuest::template::{Dependency, Template};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+#[derive(Deserialize)]
+struct DivideArgs {
+    a: i64,
+    b: i64,
+}
+
+fn registry<S: Clone + Send + Sync + 'static>() -> FunctionRegistry<S> {
+    let definition = Definition::new("Divide", NodeKind::FUNCTION)
+        .args(vec![Port::new_int("a").build(), Port::new_int("b").build()])
+        .returns(vec![Port::new_int("quotient").build()])
+        .build();
+    let template = Template::new("divide", definition)
+        .params(json!({ "precise": true }))
+        .dependency(Dependency::new("log", "abc").optional(true));
+
+    let mut registry = FunctionRegistry::new();
+    registry
+        .register(
+            template.build(),
+            |_: Context<S>, args: DivideArgs| async move {
+                match args.b {
+                    0 => Err("division by zero".to_string()),
+                    b => Ok(json!({ "quotient": args.a / b })),
+                }
+            },
+        )
+        .unwrap();
+    registry
+}
+
+fn args(a: i64, b: i64) -> ValueMap {
+    json!({ "a": a, "b": b }).as_object().unwrap().clone()
+}
+
+async fn serve(mock: &MockRekuest) {
+    let agent =
+        AgentBuilder::new(mock.fakt(), "token")
+            .unwrap()
+            .extension("default", registry(), ());
+    tokio::spawn(async move {
+        let _ = agent.run().await.map_err(|e| e.to_string());
+    });
+    mock.wait_for_agent().await;
+}
+
+#[tokio::test]
+async fn assignations_yield_and_finish() {
+    let mut mock = MockRekuest::start().await.unwrap();
+    serve(&mock).await;
+
+    let assignation = mock.assign("divide", args(7, 2)).unwrap();
+
+    let event = mock.next_event().await.unwrap();
+    assert_eq!(event.assignation, assignation);
+    assert_eq!(event.kind, "YIELD");
+    assert_eq!(event.returns.unwrap()["quotient"], json!(3));
+
+    let event = mock.next_event().await.unwrap();
+    assert_eq!(event.assignation, assignation);
+    assert_eq!(event.kind, "DONE");
+}
+
+#[tokio::test]
+async fn failing_functions_report_an_error() {
+    let mut mock = MockRekuest::start().await.unwrap();
+    serve(&mock).await;
+
+    let assignation = mock.assign("divide", args(1, 0)).unwrap();
+
+    let event = mock.next_event().await.unwrap();
+    assert_eq!(event.assignation, assignation);
+    assert_eq!(event.kind, "ERROR");
+    assert!(event.message.unwrap().contains("division by zero"));
+}
+
+#[tokio::test]
+async fn invalid_args_report_an_error() {
+    let mut mock = MockRekuest::start().await.unwrap();
+    serve(&mock).await;
+
+    let assignation = mock
+        .assign("divide", json!({ "a": 1 }).as_object().unwrap().clone())
+        .unwrap();
+
+    let event = mock.next_event().await.unwrap();
+    assert_eq!(event.assignation, assignation);
+    assert_eq!(event.kind, "ERROR");
+}
+
+#[tokio::test]
+async fn unknown_provisions_report_an_error() {
+    let mut mock = MockRekuest::start().await.unwrap();
+    serve(&mock).await;
+
+    mock.send(&AgentMessage::Assign {
+        assignation: 999,
+        args: args(1, 1),
+        provision: 998,
+        assignment: Assignment::default(),
+    })
+    .unwrap();
+
+    let event = mock.next_event().await.unwrap();
+    assert_eq!(event.assignation, 999);
+    assert_eq!(event.kind, "ERROR");
+    assert!(event.message.unwrap().contains("provision 998"));
+
+    // The agent keeps serving.
+    let assignation = mock.assign("divide", args(4, 2)).unwrap();
+    let event = mock.next_event().await.unwrap();
+    assert_eq!(event.assignation, assignation);
+    assert_eq!(event.kind, "YIELD");
+}
+
+#[derive(Deserialize)]
+struct HalveArgs {
+    a: i64,
+}
+
+#[tokio::test]
+async fn functions_can_call_templates_of_their_own_agent() {
+    // The template id of `divide`, known once the agent synced.
+    type DivideTemplate = Arc<OnceLock<String>>;
+
+    let definition = Definition::new("Halve", NodeKind::FUNCTION)
+        .args(vec![Port::new_int("a").build()])
+        .returns(vec![Port::new_int("half").build()])
+        .build();
+    let mut registry = registry::<DivideTemplate>();
+    registry
+        .register(
+            Template::new("halve", definition).build(),
+            |context: Context<DivideTemplate>, args: HalveArgs| async move {
+                let client = context.client().ok_or("no client")?;
+                let divide = context.state().get().ok_or("divide is not synced")?;
+                let returns: Value = client
+                    .call(Target::template(divide), &json!({ "a": args.a, "b": 2 }))
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .timeout(Duration::from_secs(5))
+                    .returns()
+                    .await
+                    .map_err(|e| e.to_string())?;
+                Ok::<_, String>(json!({ "half": returns["quotient"] }))
+            },
+        )
+        .unwrap();
+
+    let mut mock = MockRekuest::start().await.unwrap();
+    let divide = DivideTemplate::default();
+    let agent = AgentBuilder::new(mock.fakt(), "token").unwrap().extension(
+        "default",
+        registry,
+        divide.clone(),
+    );
+    tokio::spawn(async move {
+        let _ = agent.run().await.map_err(|e| e.to_string());
+    });
+    mock.wait_for_agent().await;
+    let (id, _) = mock
+        .templates()
+        .into_iter()
+        .find(|(_, interface)| interface == "divide")
+        .unwrap();
+    divide.set(id).unwrap();
+
+    let assignation = mock
+        .assign("halve", json!({ "a": 9 }).as_object().unwrap().clone())
+        .unwrap();
+
+    // Events of the nested `divide` assignation arrive in between.
+    let mut kinds = Vec::new();
+    while kinds.last().map(String::as_str) != Some("DONE") {
+        let event = tokio::time::timeout(Duration::from_secs(10), mock.next_event())
+            .await
+            .expect("halve did not finish")
+            .unwrap();
+        if event.assignation != assignation {
+            continue;
+        }
+        if event.kind == "YIELD" {
+            assert_eq!(event.returns.unwrap()["half"], json!(4));
+        }
+        assert_ne!(event.kind, "ERROR", "{:?}", event.message);
+        kinds.push(event.kind);
+    }
+    assert_eq!(kinds, vec!["YIELD", "DONE"]);
+}
+
+#[tokio::test]
+async fn synced_templates_do_not_drift() {
+    let mock = MockRekuest::start().await.unwrap();
+    let client = RekuestClient::new(mock.fakt(), "token").unwrap();
+    let agent = create_agent(&client, DEFAULT_INSTANCE_ID, "test", vec!["default"])
+        .await
+        .unwrap();
+
+    // `hash_definition` of the Divide definition, computed with python's
+    // `json.dumps(..., sort_keys=True)` and `hashlib.sha256`.
+    mock.set_node_hash(
+        "divide",
+        "04c3e0b9d1ba700ea670eeb088e09770184238b9a2f64a997f871d38b3e143cc",
+    );
+    let mut registry = registry::<()>();
+    assert_eq!(
+        registry
+            .drift(&client, &agent.id, "default")
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+    registry
+        .sync(&client, &agent, "default", true)
+        .await
+        .unwrap();
+    assert!(registry
+        .drift(&client, &agent.id, "default")
+        .await
+        .unwrap()
+        .is_empty());
+    // Other extensions of the agent are not mixed in.
+    assert!(registry
+        .drift(&client, &agent.id, "other")
+        .await
+        .unwrap()
+        .iter()
+        .all(|drift| drift.to_string().starts_with('+')));
+}
+
+#[tokio::test]
+async fn cleanup_deletes_templates_that_are_no_longer_registered() {
+    let mock = MockRekuest::start().await.unwrap();
+    let client = RekuestClient::new(mock.fakt(), "token").unwrap();
+    let agent = create_agent(&client, DEFAULT_INSTANCE_ID, "test", vec!["default"])
+        .await
+        .unwrap();
+
+    let definition = Definition::new("Halve", NodeKind::FUNCTION)
+        .args(vec![Port::new_int("a").build()])
+        .returns(vec![Port::new_int("half").build()])
+        .build();
+    let mut previous = registry::<()>();
+    previous
+        .register(
+            Template::new("halve", definition).build(),
+            |_: Context<()>, args: HalveArgs| async move {
+                Ok::<_, String>(json!({ "half": args.a / 2 }))
+            },
+        )
+        .unwrap();
+    previous
+        .sync(&client, &agent, "default", false)
+        .await
+        .unwrap();
+
+    let interfaces = |mock: &MockRekuest| -> Vec<String> {
+        let mut interfaces: Vec<String> = mock.templates().into_iter().map(|(_, i)| i).collect();
+        interfaces.sort();
+        interfaces
+    };
+    let mut registry = registry::<()>();
+    registry
+        .sync(&client, &agent, "default", false)
+        .await
+        .unwrap();
+    assert_eq!(interfaces(&mock), vec!["divide", "halve"]);
+
+    registry
+        .sync(&client, &agent, "default", true)
+        .await
+        .unwrap();
+    assert_eq!(interfaces(&mock), vec!["divide"]);
+}
+
+#[tokio::test]
+async fn manifests_are_diffed_against_one_agent() {
+    let mock = MockRekuest::start().await.unwrap();
+    let client = RekuestClient::new(mock.fakt(), "token").unwrap();
+    let agent = create_agent(&client, DEFAULT_INSTANCE_ID, "test", vec!["default"])
+        .await
+        .unwrap();
+    let other = create_agent(&client, "other", "test", vec!["default"])
+        .await
+        .unwrap();
+
+    // See `synced_templates_do_not_drift`.
+    mock.set_node_hash(
+        "divide",
+        "04c3e0b9d1ba700ea670eeb088e09770184238b9a2f64a997f871d38b3e143cc",
+    );
+    let mut registry = registry::<()>();
+    registry
+        .sync(&client, &agent, "default", false)
+        .await
+        .unwrap();
+
+    let manifest = registry.manifest().unwrap();
+    assert!(manifest
+        .diff(&client, &agent.id, "default")
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        manifest
+            .diff(&client, &other.id, "default")
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+}
@@ -0,0 +1,117 @@
+#![cfg(feature = "mock")]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use arkirust::rekuest::agent::AgentBuilder;
+use arkirust::rekuest::agent_protocol::Assignment;
+use arkirust::rekuest::api::create_template::NodeKind;
+use arkirust::rekuest::context::Context;
+use arkirust::rekuest::definition::Definition;
+use arkirust::rekuest::mock::MockRekuest;
+use arkirust::rekuest::ports::Port;
+use arkirust::rekuest::registry::{FunctionRegistry, ValueMap};
+use arkirust::rekuest::template::Template;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize)]
+struct AddArgs {
+    a: i64,
+    b: i64,
+}
+
+fn map(value: Value) -> ValueMap {
+    value.as_object().unwrap().clone()
+}
+
+/// Assign `add` and return the yielded sum, checking the DONE that follows.
+async fn assign(mock: &mut MockRekuest, args: Value, assignment: Assignment) -> Value {
+    let assignation = mock.assign_with("add", map(args), assignment).unwrap();
+
+    let event = mock.next_event().await.unwrap();
+    assert_eq!(event.assignation, assignation);
+    assert_eq!(event.kind, "YIELD");
+    let sum = event.returns.unwrap()["sum"].clone();
+
+    let event = mock.next_event().await.unwrap();
+    assert_eq!(event.kind, "DONE");
+    sum
+}
+
+#[tokio::test]
+async fn cache_is_read_with_cached_and_written_unless_ephemeral() {
+    let definition = Definition::new("Add", NodeKind::FUNCTION)
+        .args(vec![Port::new_int("a").build(), Port::new_int("b").build()])
+        .returns(vec![Port::new_int("sum").build()])
+        .build();
+    let mut registry = FunctionRegistry::<Arc<AtomicUsize>>::new();
+    registry
+        .register(
+            Template::new("add", definition).build(),
+            |context: Context<Arc<AtomicUsize>>, args: AddArgs| async move {
+                context.state().fetch_add(1, Ordering::SeqCst);
+                Ok::<_, String>(json!({ "sum": args.a + args.b }))
+            },
+        )
+        .unwrap();
+    registry.cache_results("add", 10).unwrap();
+
+    let runs = Arc::new(AtomicUsize::new(0));
+    let mut mock = MockRekuest::start().await.unwrap();
+    let agent = AgentBuilder::new(mock.fakt(), "token").unwrap().extension(
+        "default",
+        registry,
+        runs.clone(),
+    );
+    tokio::spawn(async move {
+        let _ = agent.run().await.map_err(|e| e.to_string());
+    });
+    mock.wait_for_agent().await;
+
+    let cached = Assignment {
+        cached: true,
+        ..Default::default()
+    };
+
+    // Without `cached` the function always runs, but its returns are stored.
+    assert_eq!(
+        assign(&mut mock, json!({ "a": 1, "b": 2 }), Assignment::default()).await,
+        3
+    );
+    assert_eq!(
+        assign(&mut mock, json!({ "a": 1, "b": 2 }), Assignment::default()).await,
+        3
+    );
+    assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+    // With `cached` the stored returns are used, whatever the key order.
+    assert_eq!(
+        assign(&mut mock, json!({ "b": 2, "a": 1 }), cached.clone()).await,
+        3
+    );
+    assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+    // A miss runs the function.
+    assert_eq!(
+        assign(&mut mock, json!({ "a": 2, "b": 2 }), cached.clone()).await,
+        4
+    );
+    assert_eq!(runs.load(Ordering::SeqCst), 3);
+
+    // Ephemeral returns are not stored.
+    let ephemeral = Assignment {
+        cached: true,
+        ephemeral: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        assign(&mut mock, json!({ "a": 3, "b": 3 }), ephemeral).await,
+        6
+    );
+    assert_eq!(
+        assign(&mut mock, json!({ "a": 3, "b": 3 }), cached).await,
+        6
+    );
+    assert_eq!(runs.load(Ordering::SeqCst), 5);
+}